@@ -1,12 +1,22 @@
+use std::f32::consts::TAU;
+
 use avian2d::prelude::*;
+use bevy::asset::RenderAssetUsages;
 use bevy::color::palettes::css;
+use bevy::ecs::query::QueryFilter;
 use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy_tnua::TnuaProximitySensor;
+use bevy_tnua::prelude::*;
 use bevy_yoleck::prelude::*;
 use bevy_yoleck::vpeol_3d::{Vpeol3dPosition, Vpeol3dRotation, Vpeol3dScale};
 use ordered_float::OrderedFloat;
 use serde::{Deserialize, Serialize};
 
-use crate::utils::CachedPbrMaker;
+use crate::During;
+use crate::moving_platform::PlatformPath;
+use crate::player_controls::PlayerInputSet;
+use crate::utils::{CachedPbrMaker, is_in_transformed_unit_square};
 
 pub struct ArenaPlugin;
 
@@ -18,22 +28,48 @@ impl Plugin for ArenaPlugin {
                 .with::<Vpeol3dScale>()
                 .with::<Vpeol3dRotation>()
                 .with::<BlockFriction>()
-                .insert_on_init(|| (IsBlock))
+                .with::<BlockSurface>()
+                .with::<BlockShape>()
+                .insert_on_init(|| IsBlock)
+        });
+        app.init_resource::<BlockAssets>();
+        app.add_yoleck_entity_type({
+            YoleckEntityType::new("KillZone")
+                .with::<Vpeol3dPosition>()
+                .with::<Vpeol3dScale>()
+                .with::<Vpeol3dRotation>()
+                .insert_on_init(|| IsKillZone)
         });
 
-        app.add_yoleck_edit_system(resize_block);
+        app.add_yoleck_edit_system(resize_rectangle::<With<IsBlock>>);
+        app.add_yoleck_edit_system(resize_rectangle::<With<IsKillZone>>);
         app.add_yoleck_edit_system(rotate_block);
         app.add_yoleck_edit_system(set_block_friction);
+        app.add_yoleck_edit_system(set_block_surface);
+        app.add_yoleck_edit_system(edit_block_shape);
 
-        app.add_systems(YoleckSchedule::Populate, populate_block);
+        app.add_systems(
+            YoleckSchedule::Populate,
+            (populate_block, populate_kill_zone),
+        );
+        app.add_systems(
+            FixedUpdate,
+            move_bodies_on_conveyors
+                .in_set(During::Gameplay)
+                .before(PlayerInputSet::Apply),
+        );
     }
 }
 
 #[derive(Component)]
 pub struct IsBlock;
 
+/// The player dies and bricks fall out when they enter it.
+#[derive(Component)]
+pub struct IsKillZone;
+
 #[derive(Component, YoleckComponent, Serialize, Deserialize, PartialEq, Clone)]
-struct BlockFriction(f32);
+pub struct BlockFriction(f32);
 
 impl Default for BlockFriction {
     fn default() -> Self {
@@ -48,30 +84,391 @@ fn set_block_friction(mut ui: ResMut<YoleckUi>, mut edit: YoleckEdit<&mut BlockF
     ui.add(egui::Slider::new(&mut friction.0, 0.0..=10.0).text("Friction"));
 }
 
+#[derive(Component, YoleckComponent, Serialize, Deserialize, PartialEq, Clone, Default)]
+pub struct BlockSurface {
+    /// Bodies touching the block are dragged along its (rotated) X axis at this speed.
+    pub conveyor_speed: f32,
+    pub restitution: f32,
+}
+
+fn set_block_surface(mut ui: ResMut<YoleckUi>, mut edit: YoleckEdit<&mut BlockSurface>) {
+    let Ok(mut surface) = edit.single_mut() else {
+        return;
+    };
+    ui.add(egui::Slider::new(&mut surface.conveyor_speed, -10.0..=10.0).text("Conveyor Speed"));
+    ui.add(egui::Slider::new(&mut surface.restitution, 0.0..=1.0).text("Bounciness"));
+}
+
+/// How fast the velocity of bodies on a conveyor approaches the conveyor's speed.
+const CONVEYOR_GRIP: f32 = 10.0;
+/// Bodies touching the conveyor at a steeper angle than this are hitting its side, not resting on
+/// it.
+const MIN_RESTING_NORMAL_Y: f32 = 0.7;
+
+/// The velocity of the conveyor under a Tnua controlled body. Tnua floats the body above the
+/// ground, so it does not touch the conveyor, and keeps the velocity at what the controls ask for -
+/// so the controls need to add this to their desired velocity.
+#[derive(Component, Default)]
+pub struct ConveyorVelocity(pub Vec2);
+fn move_bodies_on_conveyors(
+    time: Res<Time>,
+    conveyors_query: Query<(Entity, &BlockSurface, &Rotation)>,
+    mut bodies_query: Query<(&RigidBody, &mut LinearVelocity), Without<TnuaController>>,
+    mut tnua_query: Query<(&TnuaProximitySensor, &mut ConveyorVelocity), With<TnuaController>>,
+    collisions: Collisions,
+) {
+    for (_, mut conveyor_velocity) in tnua_query.iter_mut() {
+        conveyor_velocity.0 = Vec2::ZERO;
+    }
+    let grip = (CONVEYOR_GRIP * time.delta_secs()).min(1.0);
+    for (conveyor_entity, surface, rotation) in conveyors_query.iter() {
+        if surface.conveyor_speed == 0.0 {
+            continue;
+        }
+        let direction = *rotation * Vec2::X;
+        let up = *rotation * Vec2::Y;
+        for contact_pair in collisions.collisions_with(conveyor_entity) {
+            // Manifold normals point from the first collider to the second.
+            let (entity, normal_sign) = if contact_pair.collider1 == conveyor_entity {
+                (contact_pair.collider2, 1.0)
+            } else {
+                (contact_pair.collider1, -1.0)
+            };
+            let is_resting = contact_pair
+                .manifolds
+                .iter()
+                .any(|manifold| MIN_RESTING_NORMAL_Y <= (normal_sign * manifold.normal).dot(up));
+            if !is_resting {
+                continue;
+            }
+            let Ok((rigid_body, mut linvel)) = bodies_query.get_mut(entity) else {
+                continue;
+            };
+            if !rigid_body.is_dynamic() {
+                continue;
+            }
+            let current_speed = linvel.dot(direction);
+            linvel.0 += direction * (surface.conveyor_speed - current_speed) * grip;
+        }
+
+        for (sensor, mut conveyor_velocity) in tnua_query.iter_mut() {
+            if sensor
+                .output
+                .as_ref()
+                .is_some_and(|output| output.entity == conveyor_entity)
+            {
+                conveyor_velocity.0 = direction * surface.conveyor_speed;
+            }
+        }
+    }
+}
+
+/// The shape of the block before it gets scaled by its `Vpeol3dScale`, fitting in a unit square.
+#[derive(Component, YoleckComponent, Serialize, Deserialize, PartialEq, Clone, Debug, Default)]
+pub enum BlockShape {
+    #[default]
+    Rectangle,
+    /// A ramp going down to the right.
+    RightTriangle,
+    Circle,
+    /// Vertices that are not on the convex hull are ignored.
+    ConvexPolygon {
+        vertices: Vec<Vec2>,
+    },
+}
+
+impl BlockShape {
+    const RECTANGLE_VERTICES: [Vec2; 4] = [
+        Vec2::new(-0.5, -0.5),
+        Vec2::new(0.5, -0.5),
+        Vec2::new(0.5, 0.5),
+        Vec2::new(-0.5, 0.5),
+    ];
+    const RIGHT_TRIANGLE_VERTICES: [Vec2; 3] = [
+        Vec2::new(-0.5, -0.5),
+        Vec2::new(0.5, -0.5),
+        Vec2::new(-0.5, 0.5),
+    ];
+
+    fn default_polygon() -> Self {
+        Self::ConvexPolygon {
+            vertices: (0..5)
+                .map(|i| 0.5 * Vec2::from_angle(0.25 * TAU + i as f32 * TAU / 5.0))
+                .collect(),
+        }
+    }
+
+    /// Points along the edge of the shape, in counter-clockwise order.
+    pub fn outline(&self) -> Vec<Vec2> {
+        match self {
+            BlockShape::Rectangle => Self::RECTANGLE_VERTICES.to_vec(),
+            BlockShape::RightTriangle => Self::RIGHT_TRIANGLE_VERTICES.to_vec(),
+            BlockShape::Circle => (0..32)
+                .map(|i| 0.5 * Vec2::from_angle(i as f32 * TAU / 32.0))
+                .collect(),
+            BlockShape::ConvexPolygon { vertices } => convex_hull(vertices),
+        }
+    }
+
+    fn collider(&self) -> Collider {
+        match self {
+            BlockShape::Rectangle => Collider::rectangle(1.0, 1.0),
+            BlockShape::RightTriangle => {
+                let [a, b, c] = Self::RIGHT_TRIANGLE_VERTICES;
+                Collider::triangle(a, b, c)
+            }
+            BlockShape::Circle => Collider::circle(0.5),
+            BlockShape::ConvexPolygon { vertices } => Collider::convex_hull(vertices.clone())
+                .unwrap_or_else(|| Collider::rectangle(1.0, 1.0)),
+        }
+    }
+}
+
+/// The same hull the collider uses, so that the mesh does not show dragged in vertices. Uses
+/// Andrew's monotone chain, which yields the hull in counter-clockwise order.
+fn convex_hull(points: &[Vec2]) -> Vec<Vec2> {
+    let mut points = points.to_vec();
+    points.sort_by_key(|point| (OrderedFloat(point.x), OrderedFloat(point.y)));
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+    let mut hull = Vec::<Vec2>::with_capacity(points.len() + 1);
+    let is_right_turn = |hull: &[Vec2], point: Vec2| {
+        let [.., a, b] = hull else {
+            return false;
+        };
+        (*b - *a).perp_dot(point - *b) <= 0.0
+    };
+    // The lower hull, from left to right.
+    for point in points.iter() {
+        while is_right_turn(&hull, *point) {
+            hull.pop();
+        }
+        hull.push(*point);
+    }
+    // The upper hull, from right to left.
+    let lower_len = hull.len();
+    for point in points.iter().rev().skip(1) {
+        while lower_len < hull.len() && is_right_turn(&hull, *point) {
+            hull.pop();
+        }
+        hull.push(*point);
+    }
+    // The last point is the first one again.
+    hull.pop();
+    hull
+}
+
+#[derive(Resource)]
+struct BlockAssets {
+    material: MeshMaterial3d<StandardMaterial>,
+    conveyor_material: MeshMaterial3d<StandardMaterial>,
+    bouncy_material: MeshMaterial3d<StandardMaterial>,
+    rectangle: Mesh3d,
+    right_triangle: Mesh3d,
+    circle: Mesh3d,
+}
+
+impl FromWorld for BlockAssets {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            material: MeshMaterial3d(world.add_asset(StandardMaterial::from_color(css::GRAY))),
+            conveyor_material: MeshMaterial3d(
+                world.add_asset(StandardMaterial::from_color(css::STEEL_BLUE)),
+            ),
+            bouncy_material: MeshMaterial3d(
+                world.add_asset(StandardMaterial::from_color(css::LIMEGREEN)),
+            ),
+            rectangle: Mesh3d(world.add_asset::<Mesh>(Cuboid::new(1.0, 1.0, 1.0))),
+            right_triangle: Mesh3d(
+                world.add_asset(extruded_polygon_mesh(&BlockShape::RIGHT_TRIANGLE_VERTICES)),
+            ),
+            circle: Mesh3d(world.add_asset(Extrusion::new(Circle::new(0.5), 1.0).mesh().build())),
+        }
+    }
+}
+
+/// A prism of depth 1 whose base is the given convex polygon, in counter-clockwise order.
+fn extruded_polygon_mesh(vertices: &[Vec2]) -> Mesh {
+    let mut positions = Vec::<[f32; 3]>::new();
+    let mut normals = Vec::<[f32; 3]>::new();
+    let mut indices = Vec::<u32>::new();
+
+    for (z, normal) in [(0.5, Vec3::Z), (-0.5, Vec3::NEG_Z)] {
+        let base = positions.len() as u32;
+        positions.extend(vertices.iter().map(|vertex| vertex.extend(z).to_array()));
+        normals.extend(vertices.iter().map(|_| normal.to_array()));
+        for i in 1..vertices.len().saturating_sub(1) as u32 {
+            if 0.0 < z {
+                indices.extend([base, base + i, base + i + 1]);
+            } else {
+                indices.extend([base, base + i + 1, base + i]);
+            }
+        }
+    }
+
+    for (a, b) in vertices.iter().zip(vertices.iter().cycle().skip(1)) {
+        let base = positions.len() as u32;
+        let normal = Vec2::new(b.y - a.y, a.x - b.x)
+            .normalize_or_zero()
+            .extend(0.0);
+        positions.extend(
+            [a.extend(-0.5), b.extend(-0.5), b.extend(0.5), a.extend(0.5)]
+                .map(|position| position.to_array()),
+        );
+        normals.extend([normal.to_array(); 4]);
+        indices.extend([base, base + 1, base + 2, base, base + 2, base + 3]);
+    }
+
+    Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+    .with_inserted_indices(Indices::U32(indices))
+}
+
 fn populate_block(
-    mut populate: YoleckPopulate<&BlockFriction, With<IsBlock>>,
-    mut pbr: CachedPbrMaker,
+    mut populate: YoleckPopulate<
+        (
+            &BlockFriction,
+            &BlockSurface,
+            &BlockShape,
+            Has<PlatformPath>,
+        ),
+        With<IsBlock>,
+    >,
+    block_assets: Res<BlockAssets>,
+    mut meshes: ResMut<Assets<Mesh>>,
 ) {
-    populate.populate(|ctx, mut cmd, BlockFriction(friction)| {
-        if ctx.is_first_time() {
+    populate.populate(
+        |ctx, mut cmd, (BlockFriction(friction), surface, shape, moving)| {
+            cmd.insert(if surface.conveyor_speed != 0.0 {
+                block_assets.conveyor_material.clone()
+            } else if 0.0 < surface.restitution {
+                block_assets.bouncy_material.clone()
+            } else {
+                block_assets.material.clone()
+            });
+            if ctx.is_first_time() {
+                cmd.insert(if moving {
+                    RigidBody::Kinematic
+                } else {
+                    RigidBody::Static
+                });
+            }
+            cmd.insert(match shape {
+                BlockShape::Rectangle => block_assets.rectangle.clone(),
+                BlockShape::RightTriangle => block_assets.right_triangle.clone(),
+                BlockShape::Circle => block_assets.circle.clone(),
+                BlockShape::ConvexPolygon { .. } => {
+                    Mesh3d(meshes.add(extruded_polygon_mesh(&shape.outline())))
+                }
+            });
+            cmd.insert(shape.collider());
+            if !ctx.is_in_editor() {
+                cmd.insert(Friction::new(*friction));
+                // So that bouncy blocks are bouncy no matter what hits them.
+                cmd.insert(
+                    Restitution::new(surface.restitution)
+                        .with_combine_rule(CoefficientCombine::Max),
+                );
+            }
+        },
+    );
+}
+
+fn populate_kill_zone(mut populate: YoleckPopulate<(), With<IsKillZone>>, mut pbr: CachedPbrMaker) {
+    populate.populate(|ctx, mut cmd, ()| {
+        // Kill zones are invisible during the game - falling into them is what should be seen.
+        if ctx.is_first_time() && ctx.is_in_editor() {
             cmd.insert(pbr.make_pbr_with(
-                || Mesh::from(Cuboid::new(1.0, 1.0, 1.0)),
-                || StandardMaterial::from_color(css::GRAY),
+                || Mesh::from(Cuboid::new(1.0, 1.0, 0.1)),
+                || StandardMaterial {
+                    base_color: css::RED.with_alpha(0.3).into(),
+                    alpha_mode: AlphaMode::Blend,
+                    ..Default::default()
+                },
             ));
-            cmd.insert(RigidBody::Static);
-            cmd.insert(Collider::rectangle(1.0, 1.0));
         }
-        if !ctx.is_in_editor() {
-            cmd.insert(Friction::new(*friction));
+    });
+}
+
+fn edit_block_shape(
+    mut ui: ResMut<YoleckUi>,
+    mut edit: YoleckEdit<(
+        &mut BlockShape,
+        &Vpeol3dPosition,
+        &Vpeol3dRotation,
+        &Vpeol3dScale,
+    )>,
+    mut knobs: YoleckKnobs,
+    mut pbr: CachedPbrMaker,
+) {
+    let Ok((mut shape, position, rotation, scale)) = edit.single_mut() else {
+        return;
+    };
+
+    ui.horizontal(|ui| {
+        for (label, option) in [
+            ("Rectangle", BlockShape::Rectangle),
+            ("Ramp", BlockShape::RightTriangle),
+            ("Circle", BlockShape::Circle),
+            ("Polygon", BlockShape::default_polygon()),
+        ] {
+            let is_selected =
+                std::mem::discriminant(shape.as_ref()) == std::mem::discriminant(&option);
+            if ui.radio(is_selected, label).clicked() && !is_selected {
+                *shape = option;
+            }
+        }
+    });
+
+    let BlockShape::ConvexPolygon { vertices } = shape.as_mut() else {
+        return;
+    };
+
+    ui.horizontal(|ui| {
+        if ui.button("Add vertex").clicked() {
+            let new_vertex = 0.5 * (vertices[0] + vertices[vertices.len() - 1]);
+            vertices.push(new_vertex);
+        }
+        if ui
+            .add_enabled(3 < vertices.len(), egui::Button::new("Remove vertex"))
+            .clicked()
+        {
+            vertices.pop();
         }
     });
+
+    let knob_pbr = pbr.make_pbr_with(
+        || Mesh::from(Sphere::new(0.3)),
+        || StandardMaterial::from_color(css::YELLOW),
+    );
+
+    let to_world =
+        |vertex: Vec2| position.0 + rotation.0 * (vertex * scale.0.truncate()).extend(0.0);
+    for (i, vertex) in vertices.iter_mut().enumerate() {
+        let mut knob = knobs.knob(("polygon-vertex", i));
+        if knob.is_new {
+            knob.cmd.insert(knob_pbr.clone());
+        }
+        knob.cmd
+            .insert(Transform::from_translation(to_world(*vertex)));
+
+        if let Some(new_knob_pos) = knob.get_passed_data::<Vec3>() {
+            let local = (rotation.0.inverse() * (*new_knob_pos - position.0)).truncate();
+            // Keep the vertices inside the unit square, so that the resize knobs stay around them.
+            *vertex = (local / scale.0.truncate()).clamp(Vec2::splat(-0.5), Vec2::splat(0.5));
+        }
+    }
 }
 
-fn resize_block(
-    mut edit: YoleckEdit<
-        (&Vpeol3dRotation, &mut Vpeol3dScale, &mut Vpeol3dPosition),
-        With<IsBlock>,
-    >,
+/// Corner knobs for entities whose mesh is a unit square scaled by their `Vpeol3dScale`.
+pub fn resize_rectangle<F: QueryFilter + 'static>(
+    mut edit: YoleckEdit<(&Vpeol3dRotation, &mut Vpeol3dScale, &mut Vpeol3dPosition), F>,
     mut knobs: YoleckKnobs,
     mut pbr: CachedPbrMaker,
 ) {
@@ -159,17 +556,50 @@ fn rotate_block(
     }
 }
 
-pub fn calculate_lowest_y(objects_query: Query<&GlobalTransform, With<IsBlock>>) -> Option<f32> {
+/// Decides when the player and the bricks are considered to have fallen off the arena.
+pub enum FallOutRule {
+    KillZones(Vec<GlobalTransform>),
+    /// Levels without kill zones fall back to checking if they are below the lowest block.
+    BelowLowestBlock(f32),
+    Nothing,
+}
+
+impl FallOutRule {
+    /// The margin is only used for the fallback, to give some distance below the lowest block.
+    pub fn has_fallen_out(&self, point: Vec2, fallback_margin: f32) -> bool {
+        match self {
+            FallOutRule::KillZones(kill_zones) => kill_zones
+                .iter()
+                .any(|transform| is_in_transformed_unit_square(transform, point)),
+            FallOutRule::BelowLowestBlock(lowest_y) => point.y < lowest_y - fallback_margin,
+            FallOutRule::Nothing => false,
+        }
+    }
+}
+
+pub fn calculate_fall_out_rule(
+    kill_zones_query: Query<&GlobalTransform, With<IsKillZone>>,
+    objects_query: Query<(&GlobalTransform, &BlockShape), With<IsBlock>>,
+) -> FallOutRule {
+    if !kill_zones_query.is_empty() {
+        FallOutRule::KillZones(kill_zones_query.iter().copied().collect())
+    } else if let Some(lowest_y) = calculate_lowest_y(&objects_query) {
+        FallOutRule::BelowLowestBlock(lowest_y)
+    } else {
+        FallOutRule::Nothing
+    }
+}
+
+fn calculate_lowest_y(
+    objects_query: &Query<(&GlobalTransform, &BlockShape), With<IsBlock>>,
+) -> Option<f32> {
     objects_query
         .iter()
-        .flat_map(|transform| {
-            static FOUR_CORNERS: [Vec3; 4] = [
-                Vec3::new(0.5, 0.5, 0.0),
-                Vec3::new(-0.5, 0.5, 0.0),
-                Vec3::new(-0.5, -0.5, 0.0),
-                Vec3::new(0.5, -0.5, 0.0),
-            ];
-            FOUR_CORNERS.map(|corner| transform.transform_point(corner).y)
+        .flat_map(|(transform, shape)| {
+            shape
+                .outline()
+                .into_iter()
+                .map(|point| transform.transform_point(point.extend(0.0)).y)
         })
         .min_by_key(|y| OrderedFloat(*y))
 }
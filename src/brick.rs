@@ -1,10 +1,13 @@
 use avian2d::prelude::*;
+use bevy::color::palettes::css;
 use bevy::prelude::*;
-use bevy_yoleck::vpeol_3d::Vpeol3dPosition;
+use bevy_yoleck::vpeol_3d::{Vpeol3dPosition, Vpeol3dRotation, Vpeol3dScale};
 use bevy_yoleck::{YoleckDirective, prelude::*};
+use serde::{Deserialize, Serialize};
 
 use crate::picking_up::Pickable;
-use crate::topple_detection::Toppleable;
+use crate::topple_detection::{MustStayStanding, SequenceNumber, StartingRotation, Toppleable};
+use crate::utils::CachedPbrMaker;
 
 pub struct BrickPlugin;
 
@@ -13,6 +16,11 @@ impl Plugin for BrickPlugin {
         app.add_yoleck_entity_type({
             YoleckEntityType::new("Brick")
                 .with::<Vpeol3dPosition>()
+                .with::<Vpeol3dScale>()
+                .with::<Vpeol3dRotation>()
+                .with::<BrickPhysics>()
+                .with::<BrickProtection>()
+                .with::<ToppleOrder>()
                 .insert_on_init_during_editor(|| Dupable("Brick"))
                 .insert_on_init(|| (IsBrick, Toppleable::Standing))
         });
@@ -20,55 +28,304 @@ impl Plugin for BrickPlugin {
         app.add_yoleck_entity_type({
             YoleckEntityType::new("PickableBrick")
                 .with::<Vpeol3dPosition>()
+                .with::<Vpeol3dScale>()
+                .with::<Vpeol3dRotation>()
+                .with::<BrickPhysics>()
+                .with::<BrickProtection>()
+                .with::<ToppleOrder>()
                 .insert_on_init(|| {
                     (
                         IsBrick,
                         Pickable {
-                            hold_at_offset: -2.0 * Vec2::Y,
+                            half_extents: 0.5 * BRICK_SIZE,
                         },
                         Toppleable::Standing,
                     )
                 })
         });
 
+        app.init_resource::<BrickMarkerAssets>();
         app.add_systems(YoleckSchedule::Populate, populate_brick);
         app.add_yoleck_edit_system(dup_buttons);
+        app.add_yoleck_edit_system(set_brick_physics);
+        app.add_yoleck_edit_system(set_brick_protection);
+        app.add_yoleck_edit_system(set_topple_order);
+        app.add_yoleck_edit_system(resize_brick);
+        app.add_yoleck_edit_system(rotate_brick);
     }
 }
 
 #[derive(Component)]
 pub struct IsBrick;
 
+/// The size of the brick model (and its collider) when it is not scaled.
+pub const BRICK_SIZE: Vec2 = Vec2::new(0.2, 4.0);
+
+#[derive(Component, YoleckComponent, Serialize, Deserialize, PartialEq, Clone)]
+pub struct BrickPhysics {
+    pub mass: f32,
+    pub friction: f32,
+    pub gravity_scale: f32,
+}
+
+impl Default for BrickPhysics {
+    fn default() -> Self {
+        Self {
+            mass: 10.0,
+            friction: 0.1,
+            gravity_scale: 5.0,
+        }
+    }
+}
+
 #[derive(Component)]
 pub struct Dupable(&'static str);
 
+#[derive(Component, YoleckComponent, Serialize, Deserialize, PartialEq, Clone, Default)]
+pub struct BrickProtection {
+    pub must_stay_standing: bool,
+}
+
+/// Bricks with a number must start falling before any brick with a higher number.
+#[derive(Component, YoleckComponent, Serialize, Deserialize, PartialEq, Clone, Default)]
+pub struct ToppleOrder {
+    pub number: Option<u32>,
+}
+
+#[derive(Resource)]
+struct BrickMarkerAssets {
+    protection: (Mesh3d, MeshMaterial3d<StandardMaterial>),
+    pip: (Mesh3d, MeshMaterial3d<StandardMaterial>),
+}
+
+impl FromWorld for BrickMarkerAssets {
+    fn from_world(world: &mut World) -> Self {
+        let protection_mesh = world.add_asset::<Mesh>(Cuboid::new(0.3, 0.4, 0.3));
+        let pip_mesh = world.add_asset::<Mesh>(Cuboid::new(0.25, 0.25, 0.25));
+        let protection_material = world.add_asset(StandardMaterial::from_color(css::RED));
+        let pip_material = world.add_asset(StandardMaterial::from_color(css::YELLOW));
+        Self {
+            protection: (Mesh3d(protection_mesh), MeshMaterial3d(protection_material)),
+            pip: (Mesh3d(pip_mesh), MeshMaterial3d(pip_material)),
+        }
+    }
+}
+
+/// The child entities that show the brick's protection and topple order.
+#[derive(Component)]
+struct BrickMarkers(Vec<Entity>);
+
 fn populate_brick(
-    mut populate: YoleckPopulate<Has<Pickable>, With<IsBrick>>,
+    mut populate: YoleckPopulate<
+        (
+            Has<Pickable>,
+            &Vpeol3dScale,
+            &Vpeol3dRotation,
+            &BrickPhysics,
+            &BrickProtection,
+            &ToppleOrder,
+            Option<&BrickMarkers>,
+        ),
+        With<IsBrick>,
+    >,
     asset_server: Res<AssetServer>,
+    marker_assets: Res<BrickMarkerAssets>,
 ) {
-    populate.populate(|ctx, mut cmd, pickable| {
-        if ctx.is_first_time() {
-            cmd.insert(bevy_yoleck::vpeol::VpeolWillContainClickableChildren);
-            cmd.insert(SceneRoot(asset_server.load(if pickable {
-                "PickableBrick.glb#Scene0"
+    populate.populate(
+        |ctx, mut cmd, (pickable, scale, rotation, physics, protection, order, markers)| {
+            if ctx.is_first_time() {
+                cmd.insert(bevy_yoleck::vpeol::VpeolWillContainClickableChildren);
+                cmd.insert(SceneRoot(asset_server.load(if pickable {
+                    "PickableBrick.glb#Scene0"
+                } else {
+                    "Brick.glb#Scene0"
+                })));
+            }
+            if pickable {
+                cmd.insert(Pickable {
+                    half_extents: 0.5 * BRICK_SIZE * scale.0.truncate(),
+                });
+            }
+            let (angle, _, _) = rotation.0.to_euler(EulerRot::ZYX);
+            cmd.insert(StartingRotation(Rotation::radians(angle)));
+            cmd.insert(RigidBody::Dynamic);
+            // The collider gets scaled together with the model by the entity's `Vpeol3dScale`.
+            cmd.insert(Collider::rectangle(BRICK_SIZE.x, BRICK_SIZE.y));
+            cmd.insert(Friction::new(physics.friction));
+            cmd.insert(Mass(physics.mass));
+            cmd.insert(GravityScale(physics.gravity_scale));
+
+            if let Some(BrickMarkers(markers)) = markers {
+                for marker in markers.iter() {
+                    cmd.commands().entity(*marker).despawn();
+                }
+            }
+            // The markers are children of the brick, so they get scaled with it. Undo that, so that
+            // they keep their size and their distance from the top of the brick.
+            let brick_top = 0.5 * BRICK_SIZE.y * scale.0.y;
+            let marker_transform = |depth: f32| {
+                Transform::from_translation(Vec3::new(0.0, brick_top - depth, 0.0) / scale.0)
+                    .with_scale(scale.0.recip())
+            };
+            let mut markers = Vec::new();
+            if protection.must_stay_standing {
+                cmd.insert(MustStayStanding);
+                markers.push((marker_assets.protection.clone(), marker_transform(0.2)));
+            } else {
+                cmd.remove::<MustStayStanding>();
+            }
+            if let Some(number) = order.number {
+                cmd.insert(SequenceNumber(number));
+                // One pip per number, going down from the top of the brick.
+                for i in 0..number {
+                    markers.push((
+                        marker_assets.pip.clone(),
+                        marker_transform(0.6 + 0.35 * i as f32),
+                    ));
+                }
             } else {
-                "Brick.glb#Scene0"
-            })));
+                cmd.remove::<SequenceNumber>();
+            }
+            let markers = markers
+                .into_iter()
+                .map(|marker| cmd.commands().spawn(marker).id())
+                .collect::<Vec<_>>();
+            cmd.add_children(&markers);
+            cmd.insert(BrickMarkers(markers));
+        },
+    );
+}
+
+fn set_brick_protection(mut ui: ResMut<YoleckUi>, mut edit: YoleckEdit<&mut BrickProtection>) {
+    let Ok(mut protection) = edit.single_mut() else {
+        return;
+    };
+    ui.checkbox(&mut protection.must_stay_standing, "Must stay standing");
+}
+
+fn set_topple_order(mut ui: ResMut<YoleckUi>, mut edit: YoleckEdit<&mut ToppleOrder>) {
+    let Ok(mut order) = edit.single_mut() else {
+        return;
+    };
+    ui.horizontal(|ui| {
+        let mut numbered = order.number.is_some();
+        if ui.checkbox(&mut numbered, "Topple order").changed() {
+            order.number = numbered.then_some(1);
+        }
+        if let Some(number) = order.number.as_mut() {
+            ui.add(egui::DragValue::new(number).range(1..=9));
         }
-        cmd.insert(RigidBody::Dynamic);
-        cmd.insert(Collider::rectangle(0.2, 4.0));
-        cmd.insert(Friction::new(0.1));
-        cmd.insert(Mass(10.0));
-        cmd.insert(GravityScale(5.0));
     });
 }
 
+fn set_brick_physics(mut ui: ResMut<YoleckUi>, mut edit: YoleckEdit<&mut BrickPhysics>) {
+    let Ok(mut physics) = edit.single_mut() else {
+        return;
+    };
+    ui.add(egui::Slider::new(&mut physics.mass, 0.1..=100.0).text("Mass"));
+    ui.add(egui::Slider::new(&mut physics.friction, 0.0..=10.0).text("Friction"));
+    ui.add(egui::Slider::new(&mut physics.gravity_scale, 0.0..=10.0).text("Gravity Scale"));
+}
+
+fn resize_brick(
+    mut edit: YoleckEdit<
+        (&Vpeol3dRotation, &mut Vpeol3dScale, &mut Vpeol3dPosition),
+        With<IsBrick>,
+    >,
+    mut knobs: YoleckKnobs,
+    mut pbr: CachedPbrMaker,
+) {
+    let Ok((rotation, mut scale, mut position)) = edit.single_mut() else {
+        return;
+    };
+
+    let knob_pbr = pbr.make_pbr_with(
+        || Mesh::from(Cuboid::new(0.4, 0.4, 1.1)),
+        || StandardMaterial::from_color(css::ORANGE),
+    );
+
+    let size = BRICK_SIZE * scale.0.truncate();
+
+    for (i, diagonal) in [
+        Vec2::new(1.0, 1.0),
+        Vec2::new(-1.0, 1.0),
+        Vec2::new(-1.0, -1.0),
+        Vec2::new(1.0, -1.0),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let rotated_offset = rotation.0 * (0.5 * diagonal * size).extend(0.0);
+        let mut knob = knobs.knob(("resize-marker", i));
+        if knob.is_new {
+            knob.cmd.insert(knob_pbr.clone());
+        }
+        knob.cmd
+            .insert(Transform::from_translation(position.0 + rotated_offset));
+
+        if let Some(new_marker_pos) = knob.get_passed_data::<Vec3>() {
+            let other_corner = position.0 - rotated_offset;
+            let new_size =
+                (rotation.0.inverse() * (*new_marker_pos - other_corner)).truncate() * diagonal;
+            // Don't let the brick flip or become too thin to grab the knobs.
+            let new_size = new_size.max(Vec2::splat(0.1));
+            scale.0 = (new_size / BRICK_SIZE).extend(1.0);
+            position.0 = other_corner + rotation.0 * (0.5 * diagonal * new_size).extend(0.0);
+        }
+    }
+}
+
+fn rotate_brick(
+    mut edit: YoleckEdit<(&mut Vpeol3dRotation, &Vpeol3dScale, &Vpeol3dPosition), With<IsBrick>>,
+    mut knobs: YoleckKnobs,
+    mut pbr: CachedPbrMaker,
+) {
+    let Ok((mut rotation, scale, position)) = edit.single_mut() else {
+        return;
+    };
+
+    let knob_pbr = pbr.make_pbr_with(
+        || Mesh::from(Sphere::new(0.4)),
+        || StandardMaterial::from_color(css::GREEN),
+    );
+
+    // Bricks are too thin for knobs on their sides, so only put them on the top and bottom.
+    for (i, knob_direction) in [Vec2::Y, Vec2::NEG_Y].into_iter().enumerate() {
+        let offset = (0.5 * BRICK_SIZE.y * scale.0.y + 0.5) * knob_direction;
+        let rotated_offset = rotation.0 * offset.extend(0.0);
+        let mut knob = knobs.knob(("rotate-marker", i));
+        if knob.is_new {
+            knob.cmd.insert(knob_pbr.clone());
+        }
+        knob.cmd
+            .insert(Transform::from_translation(position.0 + rotated_offset));
+
+        if let Some(new_marker_pos) = knob.get_passed_data::<Vec3>() {
+            let desired_direction = (*new_marker_pos - position.0)
+                .truncate()
+                .normalize_or_zero();
+            rotation.0 = Quat::from_rotation_arc_2d(knob_direction, desired_direction);
+        }
+    }
+}
+
 fn dup_buttons(
     mut ui: ResMut<YoleckUi>,
-    edit: YoleckEdit<(&YoleckBelongsToLevel, &Vpeol3dPosition, &Dupable)>,
+    edit: YoleckEdit<(
+        &YoleckBelongsToLevel,
+        &Vpeol3dPosition,
+        &Vpeol3dScale,
+        &Vpeol3dRotation,
+        &BrickPhysics,
+        &BrickProtection,
+        &ToppleOrder,
+        &Dupable,
+    )>,
     mut writer: EventWriter<YoleckDirective>,
 ) {
-    let Ok((belongs_to_level, position, dupable)) = edit.single() else {
+    let Ok((belongs_to_level, position, scale, rotation, physics, protection, order, dupable)) =
+        edit.single()
+    else {
         return;
     };
 
@@ -77,6 +334,11 @@ fn dup_buttons(
             writer.write({
                 YoleckDirective::spawn_entity(belongs_to_level.level, dupable.0, true)
                     .with(Vpeol3dPosition(position.0 + direction * 3.5))
+                    .with(Vpeol3dScale(scale.0))
+                    .with(Vpeol3dRotation(rotation.0))
+                    .with(physics.clone())
+                    .with(protection.clone())
+                    .with(order.clone())
                     .modify_exclusive_systems(|queue| queue.clear())
                     .into()
             });
@@ -0,0 +1,269 @@
+use avian2d::prelude::*;
+use bevy::color::palettes::css;
+use bevy::platform::collections::HashSet;
+use bevy::prelude::*;
+use bevy_yoleck::exclusive_systems::{YoleckExclusiveSystemDirective, YoleckExclusiveSystemsQueue};
+use bevy_yoleck::prelude::*;
+use bevy_yoleck::vpeol::vpeol_read_click_on_entity;
+use bevy_yoleck::vpeol_3d::{Vpeol3dPosition, Vpeol3dRotation, Vpeol3dScale};
+use bevy_yoleck::yoleck_map_entity_to_uuid;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::During;
+use crate::arena::resize_rectangle;
+use crate::player::IsPlayer;
+use crate::topple_detection::Toppleable;
+
+pub struct ContraptionsPlugin;
+
+impl Plugin for ContraptionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_yoleck_entity_type({
+            YoleckEntityType::new("Trigger")
+                .with_uuid()
+                .with::<Vpeol3dPosition>()
+                .with::<Vpeol3dScale>()
+                .with::<Vpeol3dRotation>()
+                .with::<TriggerSettings>()
+                .insert_on_init(TriggerState::default)
+        });
+        app.add_yoleck_entity_type({
+            YoleckEntityType::new("Door")
+                .with_uuid()
+                .with::<Vpeol3dPosition>()
+                .with::<Vpeol3dScale>()
+                .with::<Vpeol3dRotation>()
+                .with::<DoorSettings>()
+        });
+        app.init_resource::<ContraptionAssets>();
+
+        app.add_yoleck_edit_system(resize_rectangle::<With<TriggerSettings>>);
+        app.add_yoleck_edit_system(resize_rectangle::<With<DoorSettings>>);
+        app.add_yoleck_edit_system(edit_trigger);
+        app.add_yoleck_edit_system(edit_door);
+
+        app.add_systems(YoleckSchedule::Populate, (populate_trigger, populate_door));
+        app.add_systems(
+            FixedUpdate,
+            (update_triggers, update_doors)
+                .chain()
+                .in_set(During::Gameplay),
+        );
+    }
+}
+
+#[derive(Component, YoleckComponent, Serialize, Deserialize, PartialEq, Clone, Default)]
+pub struct TriggerSettings {
+    pub kind: TriggerKind,
+    /// The doors toggled by this trigger.
+    pub targets: Vec<Uuid>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug, Default)]
+pub enum TriggerKind {
+    /// Active only while the player or a toppled brick is on it.
+    #[default]
+    PressurePlate,
+    /// Stays active once the player or a toppled brick touches it.
+    Switch,
+}
+
+#[derive(Component, Default, Clone, Debug)]
+pub struct TriggerState {
+    pub active: bool,
+}
+
+#[derive(Component, YoleckComponent, Serialize, Deserialize, PartialEq, Clone, Default)]
+pub struct DoorSettings {
+    /// Doors start closed and open when triggered. Setting this makes them start open and close
+    /// when triggered instead (e.g. for bridges).
+    pub starts_open: bool,
+}
+
+#[derive(Resource)]
+struct ContraptionAssets {
+    plate_mesh: Mesh3d,
+    door_mesh: Mesh3d,
+    inactive_material: MeshMaterial3d<StandardMaterial>,
+    active_material: MeshMaterial3d<StandardMaterial>,
+    door_material: MeshMaterial3d<StandardMaterial>,
+}
+
+impl FromWorld for ContraptionAssets {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            plate_mesh: Mesh3d(world.add_asset::<Mesh>(Cuboid::new(1.0, 1.0, 1.0))),
+            door_mesh: Mesh3d(world.add_asset::<Mesh>(Cuboid::new(1.0, 1.0, 0.8))),
+            inactive_material: MeshMaterial3d(
+                world.add_asset(StandardMaterial::from_color(css::DARK_RED)),
+            ),
+            active_material: MeshMaterial3d(
+                world.add_asset(StandardMaterial::from_color(css::LIME)),
+            ),
+            door_material: MeshMaterial3d(
+                world.add_asset(StandardMaterial::from_color(css::SIENNA)),
+            ),
+        }
+    }
+}
+
+fn populate_trigger(
+    mut populate: YoleckPopulate<(), With<TriggerSettings>>,
+    assets: Res<ContraptionAssets>,
+) {
+    populate.populate(|ctx, mut cmd, ()| {
+        if ctx.is_first_time() {
+            cmd.insert((assets.plate_mesh.clone(), assets.inactive_material.clone()));
+            cmd.insert(RigidBody::Static);
+            cmd.insert(Collider::rectangle(1.0, 1.0));
+            cmd.insert(Sensor);
+            cmd.insert(CollidingEntities::default());
+        }
+    });
+}
+
+fn populate_door(
+    mut populate: YoleckPopulate<(), With<DoorSettings>>,
+    assets: Res<ContraptionAssets>,
+) {
+    populate.populate(|ctx, mut cmd, ()| {
+        if ctx.is_first_time() {
+            cmd.insert((assets.door_mesh.clone(), assets.door_material.clone()));
+            cmd.insert(RigidBody::Static);
+            cmd.insert(Collider::rectangle(1.0, 1.0));
+        }
+    });
+}
+
+fn edit_trigger(
+    mut ui: ResMut<YoleckUi>,
+    mut edit: YoleckEdit<&mut TriggerSettings>,
+    doors_query: Query<&YoleckEntityUuid, With<DoorSettings>>,
+    mut exclusive_queue: ResMut<YoleckExclusiveSystemsQueue>,
+) {
+    let Ok(mut trigger) = edit.single_mut() else {
+        return;
+    };
+
+    ui.horizontal(|ui| {
+        ui.radio_value(
+            &mut trigger.kind,
+            TriggerKind::PressurePlate,
+            "Pressure Plate",
+        );
+        ui.radio_value(&mut trigger.kind, TriggerKind::Switch, "Switch");
+    });
+
+    // Doors that were deleted can't be toggled anyway.
+    let existing_doors = doors_query
+        .iter()
+        .map(|uuid| uuid.get())
+        .collect::<HashSet<_>>();
+    trigger
+        .targets
+        .retain(|target| existing_doors.contains(target));
+
+    ui.label(format!("Toggles {} door(s)", trigger.targets.len()));
+    ui.horizontal(|ui| {
+        if ui.button("Add door").clicked() {
+            exclusive_queue.push_back(
+                vpeol_read_click_on_entity::<With<DoorSettings>>
+                    .pipe(yoleck_map_entity_to_uuid)
+                    .pipe(
+                        |In(target): In<Option<Uuid>>,
+                         mut edit: YoleckEdit<&mut TriggerSettings>| {
+                            let Ok(mut trigger) = edit.single_mut() else {
+                                return YoleckExclusiveSystemDirective::Finished;
+                            };
+                            let Some(target) = target else {
+                                return YoleckExclusiveSystemDirective::Listening;
+                            };
+                            if !trigger.targets.contains(&target) {
+                                trigger.targets.push(target);
+                            }
+                            YoleckExclusiveSystemDirective::Finished
+                        },
+                    ),
+            );
+        }
+        if ui.button("Clear").clicked() {
+            trigger.targets.clear();
+        }
+    });
+}
+
+fn edit_door(mut ui: ResMut<YoleckUi>, mut edit: YoleckEdit<&mut DoorSettings>) {
+    let Ok(mut door) = edit.single_mut() else {
+        return;
+    };
+    ui.checkbox(&mut door.starts_open, "Starts open");
+}
+
+fn update_triggers(
+    mut triggers_query: Query<(
+        &TriggerSettings,
+        &mut TriggerState,
+        &CollidingEntities,
+        &mut MeshMaterial3d<StandardMaterial>,
+    )>,
+    activators_query: Query<(Has<IsPlayer>, Option<&Toppleable>)>,
+    assets: Res<ContraptionAssets>,
+) {
+    for (trigger, mut state, colliding_entities, mut material) in triggers_query.iter_mut() {
+        let pressed = colliding_entities
+            .iter()
+            .any(|entity| match activators_query.get(*entity) {
+                Ok((true, _)) => true,
+                Ok((false, Some(toppleable))) => !matches!(toppleable, Toppleable::Standing),
+                _ => false,
+            });
+        let active = match trigger.kind {
+            TriggerKind::PressurePlate => pressed,
+            TriggerKind::Switch => state.active || pressed,
+        };
+        if state.active != active {
+            state.active = active;
+        }
+        // Also catches the state being restored by a rewind.
+        if state.is_changed() {
+            *material = if state.active {
+                assets.active_material.clone()
+            } else {
+                assets.inactive_material.clone()
+            };
+        }
+    }
+}
+
+fn update_doors(
+    triggers_query: Query<(&TriggerSettings, &TriggerState)>,
+    uuid_registry: Res<YoleckUuidRegistry>,
+    mut doors_query: Query<(
+        Entity,
+        &DoorSettings,
+        &mut Visibility,
+        Has<ColliderDisabled>,
+    )>,
+    mut commands: Commands,
+) {
+    let triggered = triggers_query
+        .iter()
+        .filter(|(_, state)| state.active)
+        .flat_map(|(trigger, _)| trigger.targets.iter())
+        .filter_map(|target| uuid_registry.get(*target))
+        .collect::<HashSet<Entity>>();
+    for (entity, door, mut visibility, disabled) in doors_query.iter_mut() {
+        let open = triggered.contains(&entity) != door.starts_open;
+        if open == disabled {
+            continue;
+        }
+        if open {
+            commands.entity(entity).insert(ColliderDisabled);
+            *visibility = Visibility::Hidden;
+        } else {
+            commands.entity(entity).remove::<ColliderDisabled>();
+            *visibility = Visibility::Inherited;
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use avian2d::prelude::*;
+use bevy::color::palettes::css;
+use bevy::prelude::*;
+use bevy_yoleck::prelude::*;
+use bevy_yoleck::vpeol_3d::{Vpeol3dPosition, Vpeol3dRotation, Vpeol3dScale};
+use serde::{Deserialize, Serialize};
+
+use crate::During;
+use crate::arena::resize_rectangle;
+use crate::picking_up::HeldBy;
+use crate::utils::{CachedPbrMaker, is_in_transformed_unit_square};
+
+pub struct ForceFieldPlugin;
+
+impl Plugin for ForceFieldPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_yoleck_entity_type({
+            YoleckEntityType::new("ForceField")
+                .with::<Vpeol3dPosition>()
+                .with::<Vpeol3dScale>()
+                .with::<Vpeol3dRotation>()
+                .with::<ForceField>()
+        });
+
+        app.add_yoleck_edit_system(resize_rectangle::<With<ForceField>>);
+        app.add_yoleck_edit_system(edit_force_field);
+
+        app.add_systems(YoleckSchedule::Populate, populate_force_field);
+        app.add_systems(FixedUpdate, apply_force_fields.in_set(During::Gameplay));
+    }
+}
+
+#[derive(Component, YoleckComponent, Serialize, Deserialize, PartialEq, Clone, Default)]
+pub struct ForceField {
+    /// Applied to the bodies inside regardless of their mass, like gravity is.
+    pub acceleration: Vec2,
+    pub gravity_scale: Option<f32>,
+}
+
+/// The acceleration from all the force fields a body is in. Bodies that are not held get it
+/// applied directly - held bodies get it through the forces that hold them.
+#[derive(Component, Clone)]
+pub struct InForceField(pub Vec2);
+
+/// The body's own `GravityScale`, to restore when it leaves the force field that overrides it.
+#[derive(Component, Clone)]
+pub struct BaseGravityScale(f32);
+
+fn edit_force_field(mut ui: ResMut<YoleckUi>, mut edit: YoleckEdit<&mut ForceField>) {
+    let Ok(mut force_field) = edit.single_mut() else {
+        return;
+    };
+    ui.add(egui::Slider::new(&mut force_field.acceleration.x, -50.0..=50.0).text("Force X"));
+    ui.add(egui::Slider::new(&mut force_field.acceleration.y, -50.0..=50.0).text("Force Y"));
+    ui.horizontal(|ui| {
+        let mut overrides_gravity = force_field.gravity_scale.is_some();
+        if ui
+            .checkbox(&mut overrides_gravity, "Override gravity scale")
+            .changed()
+        {
+            force_field.gravity_scale = overrides_gravity.then_some(0.0);
+        }
+        if let Some(gravity_scale) = force_field.gravity_scale.as_mut() {
+            ui.add(egui::Slider::new(gravity_scale, -10.0..=10.0));
+        }
+    });
+}
+
+fn populate_force_field(
+    mut populate: YoleckPopulate<(), With<ForceField>>,
+    mut pbr: CachedPbrMaker,
+) {
+    populate.populate(|ctx, mut cmd, ()| {
+        if ctx.is_first_time() {
+            cmd.insert(pbr.make_pbr_with(
+                || Mesh::from(Cuboid::new(1.0, 1.0, 0.1)),
+                || StandardMaterial {
+                    base_color: css::AQUA.with_alpha(0.2).into(),
+                    alpha_mode: AlphaMode::Blend,
+                    ..Default::default()
+                },
+            ));
+        }
+    });
+}
+
+fn apply_force_fields(
+    time: Res<Time>,
+    force_fields_query: Query<(&GlobalTransform, &ForceField)>,
+    mut bodies_query: Query<(
+        Entity,
+        &RigidBody,
+        &Position,
+        &mut LinearVelocity,
+        Option<&mut GravityScale>,
+        Option<&BaseGravityScale>,
+        Option<&mut InForceField>,
+        Has<HeldBy>,
+    )>,
+    mut commands: Commands,
+) {
+    for (
+        entity,
+        rigid_body,
+        position,
+        mut linvel,
+        gravity_scale,
+        base_gravity_scale,
+        in_force_field,
+        held,
+    ) in bodies_query.iter_mut()
+    {
+        if !rigid_body.is_dynamic() {
+            continue;
+        }
+        let mut acceleration = Vec2::ZERO;
+        let mut gravity_scale_override = None;
+        for (transform, force_field) in force_fields_query.iter() {
+            if is_in_transformed_unit_square(transform, position.0) {
+                acceleration += force_field.acceleration;
+                gravity_scale_override = force_field.gravity_scale.or(gravity_scale_override);
+            }
+        }
+
+        let mut cmd = commands.entity(entity);
+        match (gravity_scale_override, base_gravity_scale, gravity_scale) {
+            (Some(new_scale), None, gravity_scale) => {
+                // Bodies without a `GravityScale` behave like they have a scale of 1.
+                let base = gravity_scale
+                    .map(|gravity_scale| gravity_scale.0)
+                    .unwrap_or(1.0);
+                cmd.insert((BaseGravityScale(base), GravityScale(new_scale)));
+            }
+            (Some(new_scale), Some(_), Some(mut gravity_scale)) => {
+                gravity_scale.0 = new_scale;
+            }
+            (None, Some(BaseGravityScale(base)), _) => {
+                cmd.insert(GravityScale(*base));
+                cmd.remove::<BaseGravityScale>();
+            }
+            (None, None, _) | (Some(_), Some(_), None) => {}
+        }
+
+        match in_force_field {
+            Some(mut in_force_field) if acceleration != Vec2::ZERO => {
+                in_force_field.0 = acceleration;
+            }
+            Some(_) => {
+                cmd.remove::<InForceField>();
+            }
+            None if acceleration != Vec2::ZERO => {
+                cmd.insert(InForceField(acceleration));
+            }
+            None => {}
+        }
+        if !held {
+            linvel.0 += acceleration * time.delta_secs();
+        }
+    }
+}
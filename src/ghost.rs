@@ -0,0 +1,276 @@
+use std::time::Duration;
+
+use avian2d::prelude::*;
+use bevy::prelude::*;
+use bevy::scene::SceneInstanceReady;
+use bevy_pkv::PkvStore;
+use serde::{Deserialize, Serialize};
+
+use crate::level_handling::LevelProgress;
+use crate::picking_up::HeldBy;
+use crate::player::{IsPlayer, PlayerFacing};
+use crate::profiles::ActiveProfile;
+use crate::rewind::{Rewind, ToppleSnapshot, take_snapshot};
+use crate::{AppState, During};
+
+pub struct GhostPlugin;
+
+impl Plugin for GhostPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GhostRecording>();
+        app.init_resource::<GhostPlayback>();
+        app.add_systems(
+            OnEnter(AppState::LoadLevel),
+            (despawn_ghosts, start_recording, start_playback),
+        );
+        app.add_systems(OnEnter(AppState::LevelCompleted), save_ghost_if_best);
+        app.add_systems(
+            FixedUpdate,
+            (
+                record_ghost_frame.after(take_snapshot),
+                play_ghost_frame.after(take_snapshot),
+            )
+                .in_set(During::Gameplay),
+        );
+        app.add_observer(rewind_recording);
+        app.add_observer(rewind_playback);
+    }
+}
+
+/// Where the player and the brick they were holding were on a single tick of the best run.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct GhostFrame {
+    player_position: Vec2,
+    facing_left: bool,
+    held_brick: Option<(Vec2, f32)>,
+    /// Bricks can be resized in the editor. Ghosts saved before it was added show unscaled bricks.
+    #[serde(default)]
+    held_brick_scale: Option<Vec2>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct GhostTrajectory {
+    frames: Vec<GhostFrame>,
+    /// Same as the level record's best time, so that the ghost shows the run of that record.
+    /// Ghosts saved before it was added have none, and get replaced by the next completion.
+    #[serde(default)]
+    attempt_time: Option<Duration>,
+}
+
+impl GhostTrajectory {
+    /// Within the profile - see [`ActiveProfile::pkv_key`].
+    pub fn pkv_key(level_key: &str) -> String {
+        format!("ghost/{level_key}")
+    }
+}
+
+#[derive(Resource, Default)]
+struct GhostRecording {
+    /// The level's key in the [`LevelProgress`].
+    level: Option<String>,
+    trajectory: GhostTrajectory,
+    /// The number of frames when the [`ToppleSnapshot`] was last taken, to truncate to on rewind.
+    frames_at_snapshot: usize,
+    snapshot_generation: u64,
+}
+
+#[derive(Resource, Default)]
+struct GhostPlayback {
+    trajectory: GhostTrajectory,
+    tick: usize,
+    /// The tick when the [`ToppleSnapshot`] was last taken, to go back to on rewind.
+    tick_at_snapshot: usize,
+    snapshot_generation: u64,
+}
+
+#[derive(Component)]
+enum Ghost {
+    Player,
+    HeldBrick,
+}
+
+fn despawn_ghosts(query: Query<Entity, With<Ghost>>, mut commands: Commands) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+fn start_recording(level_progress: Res<LevelProgress>, mut recording: ResMut<GhostRecording>) {
+    *recording = GhostRecording {
+        level: level_progress
+            .current_level
+            .as_deref()
+            .map(|level| level_progress.level_key(level).to_owned()),
+        ..Default::default()
+    };
+}
+
+fn record_ghost_frame(
+    mut recording: ResMut<GhostRecording>,
+    topple_snapshot: Res<ToppleSnapshot>,
+    players_query: Query<(&Position, &PlayerFacing), With<IsPlayer>>,
+    held_query: Query<(&Position, &Rotation, &Transform), With<HeldBy>>,
+) {
+    let Ok((player_position, facing)) = players_query.single() else {
+        return;
+    };
+    if recording.snapshot_generation != topple_snapshot.generation() {
+        recording.snapshot_generation = topple_snapshot.generation();
+        recording.frames_at_snapshot = recording.trajectory.frames.len();
+    }
+    let held = held_query.iter().next();
+    recording.trajectory.frames.push(GhostFrame {
+        player_position: player_position.0,
+        facing_left: matches!(facing, PlayerFacing::Left),
+        held_brick: held.map(|(position, rotation, _)| (position.0, rotation.as_radians())),
+        held_brick_scale: held.map(|(_, _, transform)| transform.scale.truncate()),
+    });
+}
+
+/// Rewinding puts the player back where they were when the snapshot was taken, so the ghost
+/// should continue from there too instead of teleporting.
+fn rewind_recording(_: Trigger<Rewind>, mut recording: ResMut<GhostRecording>) {
+    let frames_at_snapshot = recording.frames_at_snapshot;
+    recording.trajectory.frames.truncate(frames_at_snapshot);
+}
+
+/// The ghost of the best run goes back along with the player, so the two stay comparable.
+fn rewind_playback(_: Trigger<Rewind>, mut playback: ResMut<GhostPlayback>) {
+    playback.tick = playback.tick_at_snapshot;
+}
+
+fn save_ghost_if_best(
+    mut recording: ResMut<GhostRecording>,
+    level_progress: Res<LevelProgress>,
+    profile: Res<ActiveProfile>,
+    mut pkv: ResMut<PkvStore>,
+) {
+    let GhostRecording {
+        level,
+        mut trajectory,
+        ..
+    } = std::mem::take(recording.as_mut());
+    let Some(level) = level else {
+        return;
+    };
+    trajectory.attempt_time = Some(level_progress.attempt_time);
+    let key = profile.pkv_key(&GhostTrajectory::pkv_key(&level));
+    if let Ok(best) = pkv.get::<GhostTrajectory>(&key)
+        && best
+            .attempt_time
+            .is_some_and(|best_time| best_time <= level_progress.attempt_time)
+    {
+        return;
+    }
+    if let Err(err) = pkv.set(&key, &trajectory) {
+        error!("Unable to save ghost: {}", err);
+    }
+}
+
+fn start_playback(
+    level_progress: Res<LevelProgress>,
+    profile: Res<ActiveProfile>,
+    pkv: Res<PkvStore>,
+    mut playback: ResMut<GhostPlayback>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    *playback = Default::default();
+    let Some(level) = level_progress.current_level.as_ref() else {
+        return;
+    };
+    let Some(trajectory) = pkv
+        .get::<GhostTrajectory>(
+            &profile.pkv_key(&GhostTrajectory::pkv_key(level_progress.level_key(level))),
+        )
+        .ok()
+        // Ghosts saved before levels had ids are keyed by the level filename.
+        .or_else(|| {
+            pkv.get(profile.pkv_key(&GhostTrajectory::pkv_key(level)))
+                .ok()
+        })
+    else {
+        return;
+    };
+    playback.trajectory = trajectory;
+    for (ghost, scene) in [
+        (Ghost::Player, "Player.glb#Scene0"),
+        (Ghost::HeldBrick, "PickableBrick.glb#Scene0"),
+    ] {
+        commands
+            .spawn((
+                ghost,
+                SceneRoot(asset_server.load(scene)),
+                Visibility::Hidden,
+            ))
+            .observe(make_ghost_translucent);
+    }
+}
+
+fn make_ghost_translucent(
+    trigger: Trigger<SceneInstanceReady>,
+    children_query: Query<&Children>,
+    mut materials_query: Query<&mut MeshMaterial3d<StandardMaterial>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for entity in children_query.iter_descendants(trigger.target()) {
+        let Ok(mut material_handle) = materials_query.get_mut(entity) else {
+            continue;
+        };
+        let Some(material) = materials.get(&material_handle.0) else {
+            continue;
+        };
+        let mut material = material.clone();
+        material.base_color.set_alpha(0.3);
+        material.alpha_mode = AlphaMode::Blend;
+        material_handle.0 = materials.add(material);
+    }
+}
+
+fn play_ghost_frame(
+    mut playback: ResMut<GhostPlayback>,
+    topple_snapshot: Res<ToppleSnapshot>,
+    mut query: Query<(&Ghost, &mut Transform, &mut Visibility)>,
+    players_query: Query<(), With<IsPlayer>>,
+) {
+    // The recording only starts once the level is loaded.
+    if players_query.is_empty() {
+        return;
+    }
+    if playback.snapshot_generation != topple_snapshot.generation() {
+        playback.snapshot_generation = topple_snapshot.generation();
+        playback.tick_at_snapshot = playback.tick;
+    }
+    let frame = playback.trajectory.frames.get(playback.tick).cloned();
+    playback.tick += 1;
+    for (ghost, mut transform, mut visibility) in query.iter_mut() {
+        let Some(frame) = frame.as_ref() else {
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+        match ghost {
+            Ghost::Player => {
+                *transform = Transform::from_translation(frame.player_position.extend(0.0))
+                    .looking_to(
+                        if frame.facing_left {
+                            Dir3::NEG_X
+                        } else {
+                            Dir3::X
+                        },
+                        Vec3::Y,
+                    );
+                *visibility = Visibility::Inherited;
+            }
+            Ghost::HeldBrick => {
+                if let Some((position, angle)) = frame.held_brick {
+                    *transform = Transform::from_translation(position.extend(0.0))
+                        .with_rotation(Quat::from_rotation_z(angle))
+                        .with_scale(frame.held_brick_scale.unwrap_or(Vec2::ONE).extend(1.0));
+                    *visibility = Visibility::Inherited;
+                } else {
+                    *visibility = Visibility::Hidden;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use std::time::Duration;
+
+use bevy::app::{PluginGroupBuilder, ScheduleRunnerPlugin};
+use bevy::prelude::*;
+use bevy::render::RenderPlugin;
+use bevy::render::settings::WgpuSettings;
+use bevy::time::TimeUpdateStrategy;
+use bevy::window::ExitCondition;
+use bevy::winit::WinitPlugin;
+
+use crate::replay::ReplayPlayback;
+use crate::topple_detection::Toppleable;
+use crate::{AppState, During, GameOverReason};
+
+pub struct HeadlessPlugin {
+    pub timeout: Duration,
+}
+
+/// [`DefaultPlugins`] without a window or a renderer.
+pub fn headless_default_plugins() -> PluginGroupBuilder {
+    DefaultPlugins
+        .set(RenderPlugin {
+            render_creation: WgpuSettings {
+                backends: None,
+                ..default()
+            }
+            .into(),
+            ..default()
+        })
+        .set(WindowPlugin {
+            primary_window: None,
+            exit_condition: ExitCondition::DontExit,
+            ..default()
+        })
+        .disable::<WinitPlugin>()
+        .add(ScheduleRunnerPlugin::run_loop(Duration::ZERO))
+}
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        // Advance exactly one fixed timestep per frame, so that the simulation does not depend on
+        // how fast the machine running it is.
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(
+            Time::<Fixed>::default().timestep(),
+        ));
+        app.insert_resource(SimulationTimeout(Timer::new(self.timeout, TimerMode::Once)));
+        app.add_systems(OnEnter(AppState::LevelCompleted), report_level_completed);
+        app.add_systems(OnEnter(AppState::GameOver), report_game_over);
+        app.add_systems(FixedUpdate, report_timeout.in_set(During::Gameplay));
+    }
+}
+
+#[derive(Resource)]
+struct SimulationTimeout(Timer);
+
+fn report_level_completed(mut exit: EventWriter<AppExit>) {
+    println!("Level completed");
+    exit.write(AppExit::Success);
+}
+
+fn report_game_over(
+    game_over_reason: Res<GameOverReason>,
+    playback: Option<Res<ReplayPlayback>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if playback.is_some_and(|playback| playback.rewinds_next()) {
+        return;
+    }
+    if let Some(reason_text) = game_over_reason.description() {
+        println!("Game over: {reason_text}");
+    } else {
+        println!("Game over");
+    }
+    exit.write(AppExit::from_code(1));
+}
+
+fn report_timeout(
+    time: Res<Time>,
+    mut timeout: ResMut<SimulationTimeout>,
+    query: Query<&Toppleable>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if !timeout.0.tick(time.delta()).just_finished() {
+        return;
+    }
+    let num_still_standing = query
+        .iter()
+        .filter(|toppleable| matches!(toppleable, Toppleable::Standing))
+        .count();
+    println!(
+        "Timed out after {:?} with {num_still_standing} tile(s) still standing",
+        timeout.0.duration()
+    );
+    exit.write(AppExit::from_code(2));
+}
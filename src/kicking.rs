@@ -0,0 +1,112 @@
+use avian2d::prelude::*;
+use bevy::prelude::*;
+use bevy_enhanced_input::prelude::*;
+use bevy_yoleck::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::brick::BRICK_SIZE;
+use crate::camera::CameraTarget;
+use crate::picking_up::HeldBy;
+use crate::player::{IsPlayer, PlayerFacing};
+use crate::topple_detection::Toppleable;
+
+pub struct KickingPlugin;
+
+impl Plugin for KickingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_observer(kick);
+        app.add_yoleck_edit_system(edit_kicker);
+    }
+}
+
+#[derive(InputAction, Debug)]
+#[input_action(output = bool)]
+pub struct PlayerKick;
+
+/// Triggered on the kicker entity when it should kick the [`Toppleable`] in front of it.
+#[derive(Event)]
+pub struct KickRequested;
+
+#[derive(Component, YoleckComponent, Serialize, Deserialize, PartialEq, Clone)]
+pub struct Kicker {
+    pub impulse: f32,
+    pub range: f32,
+}
+
+impl Default for Kicker {
+    fn default() -> Self {
+        Self {
+            impulse: 20.0,
+            range: 3.0,
+        }
+    }
+}
+
+/// How high above the kicker's center the kick is aimed.
+const KICK_HEIGHT: f32 = 0.5;
+/// How far up from its center the kick lands on the toppleable, relative to its half height.
+const KICK_LEVER_FRACTION: f32 = 0.8;
+
+fn edit_kicker(mut ui: ResMut<YoleckUi>, mut edit: YoleckEdit<&mut Kicker, With<IsPlayer>>) {
+    let Ok(mut kicker) = edit.single_mut() else {
+        return;
+    };
+    ui.add(egui::Slider::new(&mut kicker.impulse, 0.0..=200.0).text("Kick Impulse"));
+    ui.add(egui::Slider::new(&mut kicker.range, 0.5..=20.0).text("Kick Range"));
+}
+
+fn kick(
+    trigger: Trigger<KickRequested>,
+    kicker_query: Query<
+        (&Kicker, &Position, &PlayerFacing),
+        // Same as with picking up - once the toppling has begun the player can't interfere.
+        With<CameraTarget>,
+    >,
+    toppleable_query: Query<
+        (&Position, &Rotation, &Transform),
+        (With<Toppleable>, Without<HeldBy>),
+    >,
+    obstacles_query: Query<(), (Without<Sensor>, Without<HeldBy>)>,
+    spatial_query: Res<SpatialQueryPipeline>,
+    mut commands: Commands,
+) {
+    let kicker_entity = trigger.target();
+    let Ok((kicker, kicker_position, facing)) = kicker_query.get(kicker_entity) else {
+        return;
+    };
+    let kick_origin = kicker_position.0 + KICK_HEIGHT * Vec2::Y;
+    let Some(hit) = spatial_query.cast_shape_predicate(
+        &Collider::rectangle(0.0, 0.5),
+        kick_origin,
+        0.0,
+        facing.direction_2d(),
+        &ShapeCastConfig {
+            max_distance: kicker.range,
+            ..Default::default()
+        },
+        &SpatialQueryFilter::from_excluded_entities([kicker_entity]),
+        // Walls and other bodies block the kick - only a brick that is hit first gets kicked.
+        &|entity| obstacles_query.contains(entity),
+    ) else {
+        return;
+    };
+    let Ok((toppleable_position, toppleable_rotation, toppleable_transform)) =
+        toppleable_query.get(hit.entity)
+    else {
+        return;
+    };
+    let impulse = kicker.impulse * *facing.direction_2d();
+    // Kicking high above the center of mass is what makes the brick tip over instead of sliding.
+    // The kick reaches about as high as the brick's center, so it's applied near the top instead.
+    // The kick is horizontal, so only the height of the brick's top corner matters for the lever.
+    let half_extents = 0.5 * BRICK_SIZE * toppleable_transform.scale.truncate();
+    let top_height = (*toppleable_rotation * Vec2::X).y.abs() * half_extents.x
+        + (*toppleable_rotation * Vec2::Y).y.abs() * half_extents.y;
+    let kick_point = Vec2::new(
+        hit.point1.x,
+        toppleable_position.y + KICK_LEVER_FRACTION * top_height,
+    );
+    let mut external_impulse = ExternalImpulse::default();
+    external_impulse.apply_impulse_at_point(impulse, kick_point, toppleable_position.0);
+    commands.entity(hit.entity).insert(external_impulse);
+}
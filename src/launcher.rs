@@ -0,0 +1,130 @@
+use avian2d::prelude::*;
+use bevy::color::palettes::css;
+use bevy::prelude::*;
+use bevy_yoleck::prelude::*;
+use bevy_yoleck::vpeol_3d::Vpeol3dPosition;
+use serde::{Deserialize, Serialize};
+
+use crate::During;
+use crate::picking_up::{HeldBy, HeldStatus, Picker};
+use crate::player::IsPlayer;
+use crate::utils::{CachedPbrMaker, collision_started_events_both_ways};
+
+pub struct LauncherPlugin;
+
+impl Plugin for LauncherPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_yoleck_entity_type({
+            YoleckEntityType::new("Launcher")
+                .with::<Vpeol3dPosition>()
+                .with::<Launcher>()
+        });
+
+        app.add_yoleck_edit_system(edit_launcher);
+
+        app.add_systems(YoleckSchedule::Populate, populate_launcher);
+        app.add_systems(FixedUpdate, launch_on_contact.in_set(During::Gameplay));
+    }
+}
+
+/// Launches the player, or the brick they carry, when they touch it.
+#[derive(Component, YoleckComponent, Serialize, Deserialize, PartialEq, Clone)]
+pub struct Launcher {
+    /// Replaces the velocity of the launched body rather than being added to it as an impulse, so
+    /// that the launch does not depend on the body's mass or on how it came at the launcher.
+    pub velocity: Vec2,
+}
+
+impl Default for Launcher {
+    fn default() -> Self {
+        Self {
+            velocity: 30.0 * Vec2::Y,
+        }
+    }
+}
+
+/// How far the editor knob is from the launcher per unit of launch velocity.
+const KNOB_DISTANCE_PER_VELOCITY: f32 = 0.2;
+
+fn edit_launcher(
+    mut ui: ResMut<YoleckUi>,
+    mut edit: YoleckEdit<(&mut Launcher, &Vpeol3dPosition)>,
+    mut knobs: YoleckKnobs,
+    mut pbr: CachedPbrMaker,
+) {
+    let Ok((mut launcher, position)) = edit.single_mut() else {
+        return;
+    };
+    ui.add(egui::Slider::new(&mut launcher.velocity.x, -50.0..=50.0).text("Launch X"));
+    ui.add(egui::Slider::new(&mut launcher.velocity.y, -50.0..=50.0).text("Launch Y"));
+
+    let knob_pbr = pbr.make_pbr_with(
+        || Mesh::from(Sphere::new(0.4)),
+        || StandardMaterial::from_color(css::GREEN),
+    );
+    let mut knob = knobs.knob("launch-velocity");
+    if knob.is_new {
+        knob.cmd.insert(knob_pbr);
+    }
+    knob.cmd.insert(Transform::from_translation(
+        position.0 + (KNOB_DISTANCE_PER_VELOCITY * launcher.velocity).extend(0.0),
+    ));
+    if let Some(new_knob_pos) = knob.get_passed_data::<Vec3>() {
+        launcher.velocity = (*new_knob_pos - position.0).truncate() / KNOB_DISTANCE_PER_VELOCITY;
+    }
+}
+
+/// Tnua floats the player's collider about 0.75 above the ground, so the pad's sensor has to reach
+/// higher than the pad itself for a player standing on it to touch it.
+const SENSOR_REACH: f32 = 1.0;
+
+fn populate_launcher(mut populate: YoleckPopulate<(), With<Launcher>>, mut pbr: CachedPbrMaker) {
+    populate.populate(|ctx, mut cmd, ()| {
+        if ctx.is_first_time() {
+            cmd.insert(pbr.make_pbr_with(
+                || Mesh::from(Cuboid::new(2.0, 0.3, 1.0)),
+                || StandardMaterial::from_color(css::ORANGE_RED),
+            ));
+            cmd.insert(RigidBody::Static);
+            cmd.insert(Collider::compound(vec![(
+                Position::from_xy(0.0, 0.5 * SENSOR_REACH),
+                Rotation::IDENTITY,
+                Collider::rectangle(2.0, 0.3 + SENSOR_REACH),
+            )]));
+            cmd.insert(Sensor);
+            cmd.insert(CollisionEventsEnabled);
+            // The sensor does not hold anything up, so the pad itself needs a solid collider.
+            cmd.with_child((Transform::default(), Collider::rectangle(2.0, 0.3)));
+        }
+    });
+}
+
+fn launch_on_contact(
+    mut reader: EventReader<CollisionStarted>,
+    launchers_query: Query<&Launcher>,
+    mut players_query: Query<&mut LinearVelocity, With<IsPlayer>>,
+    mut held_query: Query<(&HeldBy, &mut LinearVelocity, &mut ExternalForce), Without<IsPlayer>>,
+    mut pickers_query: Query<&mut Picker>,
+    mut commands: Commands,
+) {
+    for (launcher_entity, other) in collision_started_events_both_ways(&mut reader) {
+        let Ok(launcher) = launchers_query.get(launcher_entity) else {
+            continue;
+        };
+        // The player keeps control of the character mid-air, so Tnua only steers the horizontal
+        // part of the launch.
+        if let Ok(mut linvel) = players_query.get_mut(other) {
+            linvel.0 = launcher.velocity;
+        } else if let Ok((&HeldBy(picker_entity), mut linvel, mut force)) =
+            held_query.get_mut(other)
+        {
+            // Same as breaking the hold - except the brick flies off.
+            force.clear();
+            linvel.0 = launcher.velocity;
+            commands.entity(other).remove::<(HeldBy, HeldStatus)>();
+            if let Ok(mut picker) = pickers_query.get_mut(picker_entity) {
+                picker.clear();
+            }
+        }
+    }
+}
@@ -1,23 +1,34 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
 use bevy::prelude::*;
 use bevy_egui::EguiContexts;
 use bevy_egui_kbgp::KbgpEguiUiCtxExt;
 use bevy_pkv::PkvStore;
 use bevy_yoleck::prelude::*;
+use serde::{Deserialize, Serialize};
 
-use crate::AppState;
 use crate::menu::FocusLabel;
+use crate::profiles::{ActiveProfile, DEFAULT_PROFILE_NAME};
+use crate::{AppState, During};
 
-pub struct LevelHandlingPlugin;
+pub struct LevelHandlingPlugin {
+    pub track_progress: bool,
+}
 
 impl Plugin for LevelHandlingPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<LevelProgress>();
-        app.add_systems(Update, read_last_finished_level);
         app.add_systems(
             OnEnter(AppState::LoadLevel),
             (unload_old_levels, launch_level_loading_command).chain(),
         );
-        app.add_systems(OnEnter(AppState::LevelCompleted), handle_level_completion);
+        if self.track_progress {
+            app.add_systems(Update, read_level_records);
+            app.add_systems(OnEnter(AppState::LoadLevel), count_attempt);
+            app.add_systems(FixedUpdate, time_attempt.in_set(During::Gameplay));
+            app.add_systems(OnEnter(AppState::LevelCompleted), handle_level_completion);
+        }
     }
 }
 
@@ -27,42 +38,251 @@ pub struct LevelProgress {
     pub current_level: Option<String>,
     pub num_levels_available: usize,
     pub level_index: Handle<YoleckLevelIndex>,
+    /// Loaded only to read their [`level_id`]s.
+    level_files: Vec<Handle<YoleckRawLevel>>,
+    /// The [`level_id`] of each level, by filename. Filled before the records are read.
+    level_ids: HashMap<String, String>,
+    /// Keyed by [`level_id`], so that they survive reordering the level index and renaming levels.
+    pub records: BTreeMap<String, LevelRecord>,
+    /// Changes made before `records` were read (e.g. when starting with `--level`), to merge into
+    /// them once they are - saving before that would overwrite the saved records. Keyed by level
+    /// filename, because the ids may not be known yet either.
+    pending_records: BTreeMap<String, LevelRecord>,
+    pub attempt_time: Duration,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct LevelRecord {
+    pub completed: bool,
+    pub best_time: Option<Duration>,
+    pub attempts: u32,
+}
+
+impl LevelRecord {
+    fn merge(&mut self, other: LevelRecord) {
+        self.completed |= other.completed;
+        self.best_time = match (self.best_time, other.best_time) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        self.attempts += other.attempts;
+    }
+}
+
+/// Every level has exactly one player, and the UUID the editor gives it stays the same when the
+/// level file is renamed, so it identifies the level. Levels saved before players had UUIDs are
+/// identified by their filename until the editor saves them again.
+fn level_id(level_filename: &str, level: &YoleckRawLevel) -> String {
+    level
+        .entries()
+        .iter()
+        .find(|entry| entry.header.type_name == "Player")
+        .and_then(|entry| entry.header.uuid)
+        .map_or_else(|| level_filename.to_owned(), |uuid| uuid.to_string())
+}
+
+/// A level file copied to start a new level keeps the player, and with it the [`level_id`] - which
+/// would make the two levels share their progress. Such levels are identified by their filenames
+/// instead.
+fn fall_back_to_filenames_on_duplicate_ids(level_ids: &mut HashMap<String, String>) {
+    let mut filenames_by_id = HashMap::<&str, Vec<&str>>::new();
+    for (filename, id) in level_ids.iter() {
+        filenames_by_id.entry(id).or_default().push(filename);
+    }
+    let mut duplicates = filenames_by_id
+        .into_values()
+        .filter(|filenames| 1 < filenames.len())
+        .flatten()
+        .map(str::to_owned)
+        .collect::<Vec<_>>();
+    if duplicates.is_empty() {
+        return;
+    }
+    duplicates.sort();
+    error!(
+        "Levels {:?} share the same id - give each its own player. Identifying them by filename",
+        duplicates
+    );
+    for filename in duplicates {
+        level_ids.insert(filename.clone(), filename);
+    }
+}
+
+/// Moves records saved under level filenames - before the level had an id - to the level ids.
+/// Returns whether anything moved.
+fn migrate_filename_keys(
+    records: &mut BTreeMap<String, LevelRecord>,
+    level_ids: &HashMap<String, String>,
+) -> bool {
+    let mut any_moved = false;
+    for (filename, id) in level_ids.iter() {
+        if filename == id {
+            continue;
+        }
+        let Some(record) = records.remove(filename) else {
+            continue;
+        };
+        records.entry(id.clone()).or_default().merge(record);
+        any_moved = true;
+    }
+    any_moved
+}
+
+impl LevelProgress {
+    /// The key of the level's progress - its [`level_id`] if it is known, or its filename if not.
+    pub fn level_key<'a>(&'a self, level_filename: &'a str) -> &'a str {
+        self.level_ids
+            .get(level_filename)
+            .map_or(level_filename, String::as_str)
+    }
+
+    pub fn record(&self, level_filename: &str) -> Option<&LevelRecord> {
+        self.records.get(self.level_key(level_filename))
+    }
+
+    /// All the levels up to the furthest completed one are available, plus the one after it.
+    fn update_num_levels_available(&mut self, level_index: &YoleckLevelIndex) {
+        self.num_levels_available = level_index
+            .iter()
+            .enumerate()
+            .filter(|(_, level)| {
+                self.record(&level.filename)
+                    .is_some_and(|record| record.completed)
+            })
+            .map(|(index, _)| index + 2)
+            .max()
+            .unwrap_or(1);
+    }
+
+    fn records_loaded(&self) -> bool {
+        0 < self.num_levels_available
+    }
+
+    /// Changes the level's record and saves it, or keeps the change until the records are read.
+    fn update_record(
+        &mut self,
+        level_filename: &str,
+        pkv: &mut PkvStore,
+        profile: &ActiveProfile,
+        update: impl FnOnce(&mut LevelRecord),
+    ) {
+        if self.records_loaded() {
+            let level_key = self.level_key(level_filename).to_owned();
+            update(self.records.entry(level_key).or_default());
+            self.save_records(pkv, profile);
+        } else {
+            update(
+                self.pending_records
+                    .entry(level_filename.to_owned())
+                    .or_default(),
+            );
+        }
+    }
+
+    fn save_records(&self, pkv: &mut PkvStore, profile: &ActiveProfile) {
+        if let Err(err) = pkv.set(profile.pkv_key(LEVEL_RECORDS_PKV_KEY), &self.records) {
+            error!("Unable to save level progress: {}", err);
+        }
+    }
+
+    /// Makes the progress get read again from the [`PkvStore`] - e.g. after switching profiles.
+    pub fn reload(&mut self) {
+        self.just_completed = None;
+        self.num_levels_available = 0;
+        self.records.clear();
+        self.pending_records.clear();
+    }
 }
 
-const LEVEL_PKV_KEY: &str = "completed_up_to_level";
+pub const LEVEL_RECORDS_PKV_KEY: &str = "level_records";
+/// Older versions only saved the last completed level.
+const LEGACY_LEVEL_PKV_KEY: &str = "completed_up_to_level";
 
-fn read_last_finished_level(
-    pkv: Res<PkvStore>,
+fn read_level_records(
+    mut pkv: ResMut<PkvStore>,
+    profile: Res<ActiveProfile>,
     mut level_progress: ResMut<LevelProgress>,
     asset_server: Res<AssetServer>,
     level_index_assets: Res<Assets<YoleckLevelIndex>>,
+    level_assets: Res<Assets<YoleckRawLevel>>,
 ) {
-    if 0 < level_progress.num_levels_available {
+    if level_progress.records_loaded() {
         return;
     }
     level_progress.level_index = asset_server.load("levels/index.yoli");
-    if let Ok(completed_up_to_level) = pkv.get::<String>(LEVEL_PKV_KEY) {
-        let Some(level_index) = level_index_assets.get(&level_progress.level_index) else {
-            return;
-        };
-        if let Some(index) = level_index.iter().enumerate().find_map(|(index, level)| {
-            if level.filename == completed_up_to_level {
-                Some(index)
+    let Some(level_index) = level_index_assets.get(&level_progress.level_index) else {
+        return;
+    };
+    if level_progress.level_ids.is_empty() {
+        if level_progress.level_files.is_empty() {
+            level_progress.level_files = level_index
+                .iter()
+                .map(|level| asset_server.load(format!("levels/{}", level.filename)))
+                .collect();
+        }
+        let mut level_ids = HashMap::new();
+        for (level, handle) in level_index.iter().zip(level_progress.level_files.iter()) {
+            let id = if let Some(level_file) = level_assets.get(handle) {
+                level_id(&level.filename, level_file)
+            } else if asset_server.load_state(handle).is_failed() {
+                level.filename.clone()
             } else {
-                None
+                return;
+            };
+            level_ids.insert(level.filename.clone(), id);
+        }
+        fall_back_to_filenames_on_duplicate_ids(&mut level_ids);
+        level_progress.level_ids = level_ids;
+        level_progress.level_files.clear();
+    }
+    if let Ok(records) =
+        pkv.get::<BTreeMap<String, LevelRecord>>(&profile.pkv_key(LEVEL_RECORDS_PKV_KEY))
+    {
+        let level_progress = level_progress.as_mut();
+        level_progress.records = records;
+        if migrate_filename_keys(&mut level_progress.records, &level_progress.level_ids) {
+            level_progress.save_records(&mut pkv, &profile);
+        }
+    } else if profile.name == DEFAULT_PROFILE_NAME
+        // Only the default profile can have progress from before profiles were added.
+        && let Ok(completed_up_to_level) = pkv.get::<String>(LEGACY_LEVEL_PKV_KEY)
+    {
+        if level_index
+            .iter()
+            .any(|level| level.filename == completed_up_to_level)
+        {
+            for level in level_index.iter() {
+                let level_key = level_progress.level_key(&level.filename).to_owned();
+                level_progress
+                    .records
+                    .entry(level_key)
+                    .or_default()
+                    .completed = true;
+                if level.filename == completed_up_to_level {
+                    break;
+                }
             }
-        }) {
-            level_progress.num_levels_available = index + 2;
+            level_progress.save_records(&mut pkv, &profile);
         } else {
             error!(
                 "Unable to find level {:?}, starting anew",
                 completed_up_to_level
             );
-            level_progress.num_levels_available = 1;
         }
-    } else {
-        level_progress.num_levels_available = 1;
     }
+    let pending_records = std::mem::take(&mut level_progress.pending_records);
+    if !pending_records.is_empty() {
+        for (filename, pending_record) in pending_records {
+            let level_key = level_progress.level_key(&filename).to_owned();
+            level_progress
+                .records
+                .entry(level_key)
+                .or_default()
+                .merge(pending_record);
+        }
+        level_progress.save_records(&mut pkv, &profile);
+    }
+    level_progress.update_num_levels_available(level_index);
 }
 
 fn unload_old_levels(query: Query<Entity, With<YoleckKeepLevel>>, mut commands: Commands) {
@@ -87,34 +307,51 @@ fn launch_level_loading_command(
     app_state.set(AppState::Game);
 }
 
+fn count_attempt(
+    mut level_progress: ResMut<LevelProgress>,
+    mut pkv: ResMut<PkvStore>,
+    profile: Res<ActiveProfile>,
+) {
+    level_progress.attempt_time = Duration::ZERO;
+    let Some(current_level) = level_progress.current_level.clone() else {
+        return;
+    };
+    level_progress.update_record(&current_level, &mut pkv, &profile, |record| {
+        record.attempts += 1;
+    });
+}
+
+fn time_attempt(time: Res<Time>, mut level_progress: ResMut<LevelProgress>) {
+    level_progress.attempt_time += time.delta();
+}
+
 fn handle_level_completion(
     mut level_progress: ResMut<LevelProgress>,
     mut next_state: ResMut<NextState<AppState>>,
     level_index_assets: Res<Assets<YoleckLevelIndex>>,
     mut pkv: ResMut<PkvStore>,
+    profile: Res<ActiveProfile>,
     mut egui_contexts: EguiContexts,
 ) {
     let finished_level_name = level_progress
         .current_level
         .take()
         .expect("current_level should be set when entering the LevelCompleted state");
-    if let Some(level_index) = level_index_assets.get(&level_progress.level_index) {
-        let index_of_finished_level = level_index.iter().enumerate().find_map(|(index, level)| {
-            if level.filename == finished_level_name {
-                Some(index)
-            } else {
-                None
-            }
-        });
-        if let Some(index_of_finished_level) = index_of_finished_level {
-            let new_num_levels_available = index_of_finished_level + 2;
-            if level_progress.num_levels_available < new_num_levels_available {
-                level_progress.num_levels_available = new_num_levels_available;
-                if let Err(err) = pkv.set_string(LEVEL_PKV_KEY, &finished_level_name) {
-                    error!("Unable to save level progress: {}", err);
-                }
-            }
+    let attempt_time = level_progress.attempt_time;
+    level_progress.update_record(&finished_level_name, &mut pkv, &profile, |record| {
+        record.completed = true;
+        if record
+            .best_time
+            .is_none_or(|best_time| attempt_time < best_time)
+        {
+            record.best_time = Some(attempt_time);
         }
+    });
+    // Until the records are read, this would make them look read.
+    if level_progress.records_loaded()
+        && let Some(level_index) = level_index_assets.get(&level_progress.level_index)
+    {
+        level_progress.update_num_levels_available(level_index);
     }
     level_progress.just_completed = Some(finished_level_name);
     egui_contexts
@@ -122,3 +359,81 @@ fn handle_level_completion(
         .kbgp_set_focus_label(FocusLabel::NextLevel);
     next_state.set(AppState::LevelSelectMenu);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level_with_player(player_header: &str) -> YoleckRawLevel {
+        serde_json::from_str(&format!(
+            r#"[{{"format_version":2,"app_format_version":0}},{{}},[
+                [{{"type":"Brick","name":""}},{{}}],
+                [{player_header},{{}}]
+            ]]"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn levels_are_identified_by_their_player() {
+        let level = level_with_player(
+            r#"{"type":"Player","name":"","uuid":"67e55044-10b1-426f-9247-bb680e5fe0c8"}"#,
+        );
+        assert_eq!(
+            level_id("Renamed.yol", &level),
+            "67e55044-10b1-426f-9247-bb680e5fe0c8"
+        );
+        let level = level_with_player(r#"{"type":"Player","name":""}"#);
+        assert_eq!(level_id("Old.yol", &level), "Old.yol");
+    }
+
+    #[test]
+    fn copied_levels_are_identified_by_their_filenames() {
+        let mut level_ids = HashMap::from([
+            ("Original.yol".to_owned(), "some-id".to_owned()),
+            ("Copy.yol".to_owned(), "some-id".to_owned()),
+            ("Other.yol".to_owned(), "other-id".to_owned()),
+        ]);
+        fall_back_to_filenames_on_duplicate_ids(&mut level_ids);
+        assert_eq!(level_ids["Original.yol"], "Original.yol");
+        assert_eq!(level_ids["Copy.yol"], "Copy.yol");
+        assert_eq!(level_ids["Other.yol"], "other-id");
+    }
+
+    #[test]
+    fn records_keyed_by_filename_move_to_the_level_id() {
+        let level_ids = HashMap::from([
+            ("With_Id.yol".to_owned(), "some-id".to_owned()),
+            ("Without_Id.yol".to_owned(), "Without_Id.yol".to_owned()),
+        ]);
+        let mut records = BTreeMap::from([
+            (
+                "With_Id.yol".to_owned(),
+                LevelRecord {
+                    completed: true,
+                    best_time: Some(Duration::from_secs(10)),
+                    attempts: 2,
+                },
+            ),
+            (
+                "some-id".to_owned(),
+                LevelRecord {
+                    completed: false,
+                    best_time: None,
+                    attempts: 1,
+                },
+            ),
+            ("Without_Id.yol".to_owned(), LevelRecord::default()),
+        ]);
+        assert!(migrate_filename_keys(&mut records, &level_ids));
+        assert_eq!(
+            records.keys().collect::<Vec<_>>(),
+            ["Without_Id.yol", "some-id"]
+        );
+        let record = &records["some-id"];
+        assert!(record.completed);
+        assert_eq!(record.best_time, Some(Duration::from_secs(10)));
+        assert_eq!(record.attempts, 3);
+        assert!(!migrate_filename_keys(&mut records, &level_ids));
+    }
+}
@@ -1,13 +1,27 @@
 mod arena;
 mod brick;
 mod camera;
+mod contraptions;
+mod force_field;
+mod ghost;
+mod headless;
+mod kicking;
+mod launcher;
 mod level_handling;
 mod menu;
+mod moving_platform;
 mod picking_up;
 mod player;
 mod player_controls;
+mod profiles;
+mod replay;
+mod rewind;
+mod throwing;
 mod topple_detection;
 mod utils;
+mod win_condition;
+
+use std::time::Duration;
 
 use bevy::ecs::schedule::ScheduleLabel;
 use bevy::prelude::*;
@@ -16,16 +30,36 @@ use bevy_yoleck::prelude::YoleckSyncWithEditorState;
 use self::arena::ArenaPlugin;
 use self::brick::BrickPlugin;
 use self::camera::TimeToToppleCameraPlugin;
+use self::contraptions::ContraptionsPlugin;
+use self::force_field::ForceFieldPlugin;
+use self::ghost::GhostPlugin;
+use self::headless::HeadlessPlugin;
+use self::kicking::KickingPlugin;
+use self::launcher::LauncherPlugin;
 use self::level_handling::{LevelHandlingPlugin, LevelProgress};
 use self::menu::MenuPlugin;
+use self::moving_platform::MovingPlatformPlugin;
 use self::picking_up::PickingUpPlugin;
 use self::player::PlayerPlugin;
 use self::player_controls::PlayerControlsPlugin;
+use self::profiles::ProfilesPlugin;
+use self::replay::ReplayPlugin;
+use self::rewind::RewindPlugin;
+use self::throwing::ThrowingPlugin;
 use self::topple_detection::ToppleDetectionPlugin;
+use self::win_condition::WinConditionPlugin;
+
+pub use self::headless::headless_default_plugins;
+pub use self::profiles::{PKV_APPLICATION, PKV_ORGANIZATION};
+pub use self::replay::ReplayMode;
 
 pub struct TimeToTopplePlugin {
     pub is_editor: bool,
     pub start_at_level: Option<String>,
+    /// When set, run the level without menus and exit once it's resolved (or once the timeout
+    /// elapses).
+    pub headless_timeout: Option<Duration>,
+    pub replay_mode: ReplayMode,
 }
 
 impl Plugin for TimeToTopplePlugin {
@@ -52,8 +86,20 @@ impl Plugin for TimeToTopplePlugin {
                 when_game: AppState::Game,
             });
         } else {
-            app.add_plugins(MenuPlugin);
-            app.add_plugins(LevelHandlingPlugin);
+            if let Some(timeout) = self.headless_timeout {
+                app.add_plugins(HeadlessPlugin { timeout });
+            } else {
+                app.add_plugins(MenuPlugin);
+                app.add_plugins(ProfilesPlugin);
+                app.add_plugins(GhostPlugin);
+            }
+            app.add_plugins(LevelHandlingPlugin {
+                track_progress: self.headless_timeout.is_none(),
+            });
+            app.add_plugins(ReplayPlugin {
+                mode: self.replay_mode.clone(),
+            });
+            app.add_plugins(RewindPlugin);
             if let Some(start_at_level) = &self.start_at_level {
                 let start_at_level = if start_at_level.ends_with(".yol") {
                     start_at_level.clone()
@@ -73,10 +119,17 @@ impl Plugin for TimeToTopplePlugin {
         // app.add_plugins(AnimatingPlugin);
         app.add_plugins(PlayerPlugin);
         app.add_plugins(ArenaPlugin);
+        app.add_plugins(MovingPlatformPlugin);
+        app.add_plugins(ContraptionsPlugin);
+        app.add_plugins(ForceFieldPlugin);
+        app.add_plugins(LauncherPlugin);
         app.add_plugins(PlayerControlsPlugin);
         app.add_plugins(BrickPlugin);
         app.add_plugins(PickingUpPlugin);
+        app.add_plugins(KickingPlugin);
+        app.add_plugins(ThrowingPlugin);
         app.add_plugins(ToppleDetectionPlugin);
+        app.add_plugins(WinConditionPlugin);
         //app.add_plugins(FloatingTextPlugin);
 
         app.add_systems(Update, enable_disable_physics);
@@ -94,6 +147,7 @@ pub enum AppState {
     MainMenu,
     PauseMenu,
     LevelSelectMenu,
+    ProfileMenu,
     LoadLevel,
     Editor,
     Game,
@@ -106,12 +160,47 @@ pub enum GameOverReason {
     Unset,
     PlayerFell,
     TilesStillStanding(usize),
+    ProtectedBrickToppled,
+    ToppledOutOfOrder(u32),
+    TooFewToppled {
+        num_toppled: usize,
+        num_required: usize,
+    },
+    ToppledTooSlowly(f32),
+    GoalNotReached,
 }
 
 impl GameOverReason {
     fn reset_when_gameplay_starts(mut res: ResMut<Self>) {
         *res = Self::Unset;
     }
+
+    pub fn description(&self) -> Option<String> {
+        match self {
+            GameOverReason::Unset => None,
+            GameOverReason::PlayerFell => Some("player fell off the arena".to_owned()),
+            GameOverReason::TilesStillStanding(1) => Some("1 tile is still standing".to_owned()),
+            GameOverReason::TilesStillStanding(num_still_standing) => {
+                Some(format!("{num_still_standing} tiles are still standing"))
+            }
+            GameOverReason::ProtectedBrickToppled => {
+                Some("a brick that must stay standing was knocked over".to_owned())
+            }
+            GameOverReason::ToppledOutOfOrder(number) => Some(format!(
+                "brick #{number} fell before all the bricks numbered below it"
+            )),
+            GameOverReason::TooFewToppled {
+                num_toppled,
+                num_required,
+            } => Some(format!(
+                "only {num_toppled} of the required {num_required} tiles toppled"
+            )),
+            GameOverReason::ToppledTooSlowly(seconds) => {
+                Some(format!("the tiles did not all topple within {seconds:.1}s"))
+            }
+            GameOverReason::GoalNotReached => Some("no tile reached the goal".to_owned()),
+        }
+    }
 }
 
 impl AppState {
@@ -120,6 +209,7 @@ impl AppState {
             AppState::MainMenu => true,
             AppState::PauseMenu => true,
             AppState::LevelSelectMenu => true,
+            AppState::ProfileMenu => true,
             AppState::LoadLevel => false,
             AppState::Editor => false,
             AppState::Game => false,
@@ -133,6 +223,7 @@ impl AppState {
 pub enum ActionForKbgp {
     Menu,
     RestartLevel,
+    Rewind,
 }
 
 fn enable_disable_physics(
@@ -1,3 +1,6 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
 use avian2d::PhysicsPlugins;
 use bevy::asset::AssetMetaCheck;
 use bevy::prelude::*;
@@ -10,7 +13,10 @@ use bevy_tnua_avian2d::TnuaAvian2dPlugin;
 use bevy_yoleck::vpeol_3d::{Vpeol3dPluginForEditor, Vpeol3dPluginForGame};
 use bevy_yoleck::{YoleckPluginForEditor, YoleckPluginForGame};
 use clap::Parser;
-use time_to_topple::{ActionForKbgp, TimeToTopplePlugin};
+use time_to_topple::{
+    ActionForKbgp, PKV_APPLICATION, PKV_ORGANIZATION, ReplayMode, TimeToTopplePlugin,
+    headless_default_plugins,
+};
 
 #[derive(Parser, Debug)]
 struct Args {
@@ -18,30 +24,53 @@ struct Args {
     editor: bool,
     #[clap(long)]
     level: Option<String>,
+    /// Simulate the level without a window, print how it ended and exit.
+    #[clap(long, requires = "level", conflicts_with = "editor")]
+    headless: bool,
+    /// Seconds of simulated time after which a headless run gives up.
+    #[clap(long, value_name = "SECONDS", default_value = "120", value_parser = parse_seconds)]
+    timeout: Duration,
+    /// Save each attempt's input to a replay file in this directory.
+    #[clap(long, value_name = "DIR", conflicts_with = "editor")]
+    record: Option<PathBuf>,
+    /// Play back the level's replay file from this directory instead of the input devices.
+    #[clap(long, value_name = "DIR", conflicts_with_all = ["editor", "record"])]
+    replay: Option<PathBuf>,
+    /// Play back the level's solution file instead of reading the input devices.
+    #[clap(long, conflicts_with_all = ["editor", "record", "replay"])]
+    solution: bool,
+}
+
+fn parse_seconds(arg: &str) -> Result<Duration, String> {
+    let seconds = arg.parse::<f32>().map_err(|err| err.to_string())?;
+    Duration::try_from_secs_f32(seconds).map_err(|_| format!("{arg} is not a number of seconds"))
 }
 
-fn main() {
+fn main() -> AppExit {
     let args = Args::parse();
 
     let mut app = App::new();
-    app.add_plugins(DefaultPlugins.set(AssetPlugin {
-        // Wasm builds will check for meta files (that don't exist) if this isn't set.
-        // This causes errors and even panics in web builds on itch.
-        // See https://github.com/bevyengine/bevy_github_ci_template/issues/48.
-        meta_check: AssetMetaCheck::Never,
-        ..default()
-    }));
-
-    app.add_plugins(EguiPlugin {
-        enable_multipass_for_primary_context: true,
-    });
+    if args.headless {
+        app.add_plugins(headless_default_plugins());
+    } else {
+        app.add_plugins(DefaultPlugins.set(AssetPlugin {
+            // Wasm builds will check for meta files (that don't exist) if this isn't set.
+            // This causes errors and even panics in web builds on itch.
+            // See https://github.com/bevyengine/bevy_github_ci_template/issues/48.
+            meta_check: AssetMetaCheck::Never,
+            ..default()
+        }));
+        app.add_plugins(EguiPlugin {
+            enable_multipass_for_primary_context: true,
+        });
+    }
     app.add_plugins((
         PhysicsPlugins::default(),
         TnuaControllerPlugin::new(FixedUpdate),
         TnuaAvian2dPlugin::new(FixedUpdate),
     ));
 
-    app.insert_resource(PkvStore::new("AeonFelis", "TimeToTopple"));
+    app.insert_resource(PkvStore::new(PKV_ORGANIZATION, PKV_APPLICATION));
 
     // app.add_plugins(RngPlugin::default());
 
@@ -54,6 +83,8 @@ fn main() {
         ));
     } else {
         app.add_plugins((YoleckPluginForGame, Vpeol3dPluginForGame));
+    }
+    if !args.editor && !args.headless {
         app.add_plugins(KbgpPlugin);
         app.insert_resource(KbgpSettings {
             disable_default_navigation: true,
@@ -73,6 +104,7 @@ fn main() {
                         KeyCode::Backspace,
                         KbgpNavCommand::user(ActionForKbgp::RestartLevel),
                     )
+                    .with_key(KeyCode::KeyR, KbgpNavCommand::user(ActionForKbgp::Rewind))
                     .with_key(KeyCode::Space, KbgpNavCommand::Click)
                     .with_key(KeyCode::KeyJ, KbgpNavCommand::Click)
                     .with_gamepad_button(
@@ -83,6 +115,10 @@ fn main() {
                         GamepadButton::Select,
                         KbgpNavCommand::user(ActionForKbgp::RestartLevel),
                     )
+                    .with_gamepad_button(
+                        GamepadButton::LeftTrigger,
+                        KbgpNavCommand::user(ActionForKbgp::Rewind),
+                    )
             },
         });
     }
@@ -90,6 +126,16 @@ fn main() {
     app.add_plugins(TimeToTopplePlugin {
         is_editor: args.editor,
         start_at_level: args.level,
+        headless_timeout: args.headless.then_some(args.timeout),
+        replay_mode: if let Some(dir) = args.record {
+            ReplayMode::Record(dir)
+        } else if let Some(dir) = args.replay {
+            ReplayMode::Playback(dir)
+        } else if args.solution {
+            ReplayMode::PlaySolution
+        } else {
+            ReplayMode::Disabled
+        },
     });
-    app.run();
+    app.run()
 }
@@ -1,9 +1,14 @@
 use bevy::prelude::*;
 use bevy_egui::{EguiContextPass, EguiContexts, egui};
 use bevy_egui_kbgp::prelude::*;
+use bevy_pkv::PkvStore;
 use bevy_yoleck::prelude::*;
 
 use crate::level_handling::LevelProgress;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::profiles::ExportedProfile;
+use crate::profiles::{ActiveProfile, is_valid_profile_name};
+use crate::rewind::{Rewind, ToppleSnapshot};
 use crate::{ActionForKbgp, AppState, During, GameOverReason};
 
 #[derive()]
@@ -23,6 +28,7 @@ impl Plugin for MenuPlugin {
                 pause_menu.run_if(in_state(AppState::PauseMenu)),
                 game_over_menu.run_if(in_state(AppState::GameOver)),
                 level_select_menu.run_if(in_state(AppState::LevelSelectMenu)),
+                profile_menu.run_if(in_state(AppState::ProfileMenu)),
                 #[cfg(not(target_arch = "wasm32"))]
                 exit_button,
                 draw_menu,
@@ -40,6 +46,8 @@ fn handle_user_kbgp_actions(
     mut egui_contexts: EguiContexts,
     mut ignore_menu_button: ResMut<IgnoreMenuButton>,
     mut next_state: ResMut<NextState<AppState>>,
+    topple_snapshot: Res<ToppleSnapshot>,
+    mut commands: Commands,
 ) {
     let egui_context = egui_contexts.ctx_mut();
     let Some(action) = egui_context.kbgp_user_action() else {
@@ -55,6 +63,11 @@ fn handle_user_kbgp_actions(
         ActionForKbgp::RestartLevel => {
             next_state.set(AppState::LoadLevel);
         }
+        ActionForKbgp::Rewind => {
+            if topple_snapshot.can_rewind() {
+                commands.trigger(Rewind);
+            }
+        }
     }
 }
 
@@ -65,6 +78,7 @@ pub enum FocusLabel {
     NextLevel,
     BackToMainMenu,
     CurrentLevel,
+    Profiles,
 }
 
 #[derive(Resource, Default)]
@@ -151,7 +165,11 @@ fn format_level_name(filename: &str) -> String {
         .replace('_', " ")
 }
 
-fn main_menu(mut frame_ui: ResMut<FrameUi>, mut next_state: ResMut<NextState<AppState>>) {
+fn main_menu(
+    mut frame_ui: ResMut<FrameUi>,
+    mut next_state: ResMut<NextState<AppState>>,
+    profile: Res<ActiveProfile>,
+) {
     let Some(ui) = frame_ui.0.as_mut() else {
         return;
     };
@@ -166,12 +184,144 @@ fn main_menu(mut frame_ui: ResMut<FrameUi>, mut next_state: ResMut<NextState<App
         ui.kbgp_clear_input();
         ui.kbgp_set_focus_label(FocusLabel::NextLevel);
     }
+    if ui
+        .button(format!("Profile: {}", profile.name))
+        .kbgp_navigation()
+        .kbgp_focus_label(FocusLabel::Profiles)
+        .clicked()
+    {
+        next_state.set(AppState::ProfileMenu);
+        ui.kbgp_clear_input();
+    }
+}
+
+fn profile_menu(
+    mut frame_ui: ResMut<FrameUi>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut profile: ResMut<ActiveProfile>,
+    mut level_progress: ResMut<LevelProgress>,
+    mut pkv: ResMut<PkvStore>,
+    mut new_profile_name: Local<String>,
+    #[cfg(not(target_arch = "wasm32"))] mut export_status: Local<Option<String>>,
+    #[cfg(not(target_arch = "wasm32"))] mut importable: Local<
+        Option<Vec<(String, std::path::PathBuf)>>,
+    >,
+) {
+    let Some(ui) = frame_ui.0.as_mut() else {
+        return;
+    };
+
+    if ui.kbgp_user_action() == Some(ActionForKbgp::Menu) {
+        ui.kbgp_set_focus_label(FocusLabel::BackToMainMenu);
+    }
+    if ui
+        .button("Back To Menu")
+        .kbgp_navigation()
+        .kbgp_focus_label(FocusLabel::BackToMainMenu)
+        .clicked()
+    {
+        next_state.set(AppState::MainMenu);
+        ui.kbgp_clear_input();
+        ui.kbgp_set_focus_label(FocusLabel::Profiles);
+    }
+
+    let mut switch_to = None;
+
+    ui.horizontal(|ui| {
+        ui.add(egui::TextEdit::singleline(&mut *new_profile_name).hint_text("New profile"));
+        let new_profile_name = new_profile_name.trim();
+        if ui
+            .add_enabled(
+                is_valid_profile_name(new_profile_name),
+                egui::Button::new("Create"),
+            )
+            .kbgp_navigation()
+            .clicked()
+        {
+            switch_to = Some(new_profile_name.to_owned());
+        }
+    });
+    if new_profile_name.contains('/') {
+        ui.label("Profile names cannot contain '/'");
+    }
+    if switch_to.is_some() {
+        new_profile_name.clear();
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        if ui
+            .button(format!("Export {}", profile.name))
+            .kbgp_navigation()
+            .clicked()
+        {
+            let path = ExportedProfile::export_path(&profile.name);
+            *export_status = Some(
+                match ExportedProfile::read_from_pkv(&profile.name, &pkv).save(&path) {
+                    Ok(()) => format!("Exported to {}", path.display()),
+                    Err(err) => format!("Unable to export: {err}"),
+                },
+            );
+            *importable = None;
+        }
+        if let Some(export_status) = export_status.as_ref() {
+            ui.label(export_status.as_str());
+        }
+        if ui
+            .button("Refresh Importable Profiles")
+            .kbgp_navigation()
+            .clicked()
+        {
+            *importable = None;
+        }
+    }
+
+    egui::ScrollArea::vertical().show(ui, |ui| {
+        for profile_name in profile.all_profiles.iter() {
+            let mut response = ui.button(profile_name.as_str()).kbgp_navigation();
+            if *profile_name == profile.name {
+                response = response.kbgp_initial_focus().highlight();
+            }
+            if response.clicked() {
+                switch_to = Some(profile_name.clone());
+            }
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        for (profile_name, path) in importable.get_or_insert_with(ExportedProfile::list_importable)
+        {
+            if ui
+                .button(format!("Import {profile_name}"))
+                .kbgp_navigation()
+                .clicked()
+            {
+                match ExportedProfile::load(path) {
+                    Ok(exported_profile) => {
+                        // Never overwrite the progress of an existing profile.
+                        let profile_name = profile.unused_name(profile_name);
+                        exported_profile.write_to_pkv(&profile_name, &mut pkv);
+                        switch_to = Some(profile_name);
+                    }
+                    Err(err) => {
+                        error!("Unable to import profile from {}: {}", path.display(), err);
+                    }
+                }
+            }
+        }
+    });
+
+    if let Some(switch_to) = switch_to {
+        profile.switch_to(&switch_to, &mut pkv);
+        level_progress.reload();
+    }
 }
 
 fn pause_menu(
     mut frame_ui: ResMut<FrameUi>,
     mut ignore_menu_button: ResMut<IgnoreMenuButton>,
     mut next_state: ResMut<NextState<AppState>>,
+    topple_snapshot: Res<ToppleSnapshot>,
+    mut commands: Commands,
 ) {
     let Some(ui) = frame_ui.0.as_mut() else {
         return;
@@ -186,6 +336,15 @@ fn pause_menu(
         ignore_menu_button.0 = true;
         next_state.set(AppState::Game);
     }
+    if ui
+        .add_enabled(topple_snapshot.can_rewind(), egui::Button::new("Rewind"))
+        .kbgp_navigation()
+        .kbgp_click_released()
+        || (ui.kbgp_user_action() == Some(ActionForKbgp::Rewind) && topple_snapshot.can_rewind())
+    {
+        commands.trigger(Rewind);
+        next_state.set(AppState::Game);
+    }
     if ui.button("Retry").kbgp_navigation().kbgp_click_released() {
         next_state.set(AppState::LoadLevel);
     }
@@ -210,6 +369,8 @@ fn game_over_menu(
     mut frame_ui: ResMut<FrameUi>,
     mut next_state: ResMut<NextState<AppState>>,
     game_over_reason: Res<GameOverReason>,
+    topple_snapshot: Res<ToppleSnapshot>,
+    mut commands: Commands,
 ) {
     let Some(ui) = frame_ui.0.as_mut() else {
         return;
@@ -220,14 +381,7 @@ fn game_over_menu(
             .strong()
             .color(egui::Color32::RED),
     );
-    if let Some(reason_text) = match *game_over_reason {
-        GameOverReason::Unset => None,
-        GameOverReason::PlayerFell => Some("player fell off the arena".to_owned()),
-        GameOverReason::TilesStillStanding(1) => Some("1 tile is still standing".to_owned()),
-        GameOverReason::TilesStillStanding(num_still_standing) => {
-            Some(format!("{num_still_standing} tiles are still standing"))
-        }
-    } {
+    if let Some(reason_text) = game_over_reason.description() {
         ui.label(
             egui::RichText::new(reason_text)
                 .size(20.0)
@@ -247,6 +401,15 @@ fn game_over_menu(
     {
         next_state.set(AppState::LoadLevel);
     }
+    if ui
+        .add_enabled(topple_snapshot.can_rewind(), egui::Button::new("Rewind"))
+        .kbgp_navigation()
+        .clicked()
+        || (ui.kbgp_user_action() == Some(ActionForKbgp::Rewind) && topple_snapshot.can_rewind())
+    {
+        commands.trigger(Rewind);
+        next_state.set(AppState::Game);
+    }
     if ui
         .button("Level Select")
         .kbgp_navigation()
@@ -326,9 +489,15 @@ fn level_select_menu(
                     ..Default::default()
                 },
             );
-            if index + 1 < level_progress.num_levels_available {
+            if let Some(record) = level_progress
+                .record(&level.filename)
+                .filter(|record| record.completed)
+            {
                 button_text.append(
-                    "(complete)",
+                    &match record.best_time {
+                        Some(best_time) => format!("(complete in {:.1}s)", best_time.as_secs_f32()),
+                        None => "(complete)".to_owned(),
+                    },
                     4.0,
                     egui::TextFormat {
                         font_id: egui::FontId {
@@ -0,0 +1,216 @@
+use avian2d::prelude::*;
+use bevy::color::palettes::css;
+use bevy::prelude::*;
+use bevy_yoleck::prelude::*;
+use bevy_yoleck::vpeol_3d::{Vpeol3dPosition, Vpeol3dRotation, Vpeol3dScale};
+use serde::{Deserialize, Serialize};
+
+use crate::During;
+use crate::arena::{BlockFriction, BlockShape, BlockSurface, IsBlock};
+use crate::utils::CachedPbrMaker;
+
+pub struct MovingPlatformPlugin;
+
+impl Plugin for MovingPlatformPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_yoleck_entity_type({
+            YoleckEntityType::new("MovingBlock")
+                .with::<Vpeol3dPosition>()
+                .with::<Vpeol3dScale>()
+                .with::<Vpeol3dRotation>()
+                .with::<BlockFriction>()
+                .with::<BlockSurface>()
+                .with::<BlockShape>()
+                .with::<PlatformPath>()
+                .with::<PlatformMovement>()
+                .insert_on_init(|| IsBlock)
+        });
+
+        app.add_yoleck_edit_system(edit_platform_path);
+        app.add_yoleck_edit_system(edit_platform_movement);
+
+        app.add_systems(YoleckSchedule::Populate, populate_platform_progress);
+        app.add_systems(FixedUpdate, move_platforms.in_set(During::Gameplay));
+    }
+}
+
+/// The waypoints the block moves through after its starting position, relative to that position.
+#[derive(Component, YoleckComponent, Serialize, Deserialize, PartialEq, Clone)]
+pub struct PlatformPath {
+    pub waypoints: Vec<Vec2>,
+}
+
+impl Default for PlatformPath {
+    fn default() -> Self {
+        Self {
+            waypoints: vec![5.0 * Vec2::X],
+        }
+    }
+}
+
+#[derive(Component, YoleckComponent, Serialize, Deserialize, PartialEq, Clone)]
+pub struct PlatformMovement {
+    pub speed: f32,
+    pub mode: PathMode,
+}
+
+impl Default for PlatformMovement {
+    fn default() -> Self {
+        Self {
+            speed: 2.0,
+            mode: PathMode::PingPong,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
+pub enum PathMode {
+    /// Go back through the waypoints once reaching the last one.
+    PingPong,
+    /// Go straight from the last waypoint to the starting position.
+    Loop,
+}
+
+#[derive(Component, Clone, Debug)]
+pub struct PlatformProgress {
+    points: Vec<Vec2>,
+    target: usize,
+    backwards: bool,
+}
+
+impl PlatformProgress {
+    fn advance(&mut self, mode: PathMode) {
+        let last = self.points.len() - 1;
+        match mode {
+            PathMode::Loop => {
+                self.target = if self.target == last {
+                    0
+                } else {
+                    self.target + 1
+                };
+            }
+            PathMode::PingPong => {
+                if self.backwards && self.target == 0 {
+                    self.backwards = false;
+                } else if !self.backwards && self.target == last {
+                    self.backwards = true;
+                }
+                if self.backwards {
+                    self.target -= 1;
+                } else {
+                    self.target += 1;
+                }
+            }
+        }
+    }
+}
+
+fn edit_platform_path(
+    mut ui: ResMut<YoleckUi>,
+    mut edit: YoleckEdit<(&Vpeol3dPosition, &mut PlatformPath)>,
+    mut knobs: YoleckKnobs,
+    mut pbr: CachedPbrMaker,
+) {
+    let Ok((position, mut path)) = edit.single_mut() else {
+        return;
+    };
+
+    ui.horizontal(|ui| {
+        if ui.button("Add waypoint").clicked() {
+            let last = path.waypoints.last().copied().unwrap_or_default();
+            path.waypoints.push(last + 3.0 * Vec2::X);
+        }
+        if ui
+            .add_enabled(
+                1 < path.waypoints.len(),
+                egui::Button::new("Remove waypoint"),
+            )
+            .clicked()
+        {
+            path.waypoints.pop();
+        }
+    });
+
+    let knob_pbr = pbr.make_pbr_with(
+        || Mesh::from(Sphere::new(0.3)),
+        || StandardMaterial::from_color(css::AQUA),
+    );
+
+    for (i, waypoint) in path.waypoints.iter_mut().enumerate() {
+        let mut knob = knobs.knob(("waypoint", i));
+        if knob.is_new {
+            knob.cmd.insert(knob_pbr.clone());
+        }
+        knob.cmd.insert(Transform::from_translation(
+            position.0 + waypoint.extend(0.0),
+        ));
+
+        if let Some(new_knob_pos) = knob.get_passed_data::<Vec3>() {
+            *waypoint = (*new_knob_pos - position.0).truncate();
+        }
+    }
+}
+
+fn edit_platform_movement(mut ui: ResMut<YoleckUi>, mut edit: YoleckEdit<&mut PlatformMovement>) {
+    let Ok(mut movement) = edit.single_mut() else {
+        return;
+    };
+    ui.add(egui::Slider::new(&mut movement.speed, 0.1..=20.0).text("Speed"));
+    ui.horizontal(|ui| {
+        ui.radio_value(&mut movement.mode, PathMode::PingPong, "Ping-pong");
+        ui.radio_value(&mut movement.mode, PathMode::Loop, "Loop");
+    });
+}
+
+fn populate_platform_progress(mut populate: YoleckPopulate<(&Vpeol3dPosition, &PlatformPath)>) {
+    populate.populate(|ctx, mut cmd, (position, path)| {
+        if ctx.is_in_editor() {
+            return;
+        }
+        let start = position.0.truncate();
+        cmd.insert(PlatformProgress {
+            points: std::iter::once(start)
+                .chain(path.waypoints.iter().map(|waypoint| start + *waypoint))
+                .collect(),
+            target: 1,
+            backwards: false,
+        });
+    });
+}
+
+fn move_platforms(
+    time: Res<Time>,
+    mut query: Query<(
+        &mut PlatformProgress,
+        &PlatformMovement,
+        &Position,
+        &mut LinearVelocity,
+    )>,
+) {
+    let delta = time.delta_secs();
+    if delta == 0.0 {
+        return;
+    }
+    for (mut progress, movement, position, mut linvel) in query.iter_mut() {
+        if progress.points.len() < 2 {
+            linvel.0 = Vec2::ZERO;
+            continue;
+        }
+        // Walk along the path for this tick, possibly passing through some waypoints, and set the
+        // velocity that gets the kinematic body to where the walk ended.
+        let mut remaining = movement.speed * delta;
+        let mut current = position.0;
+        for _ in 0..progress.points.len() {
+            let target = progress.points[progress.target];
+            let distance = current.distance(target);
+            if remaining < distance {
+                current += (target - current) * (remaining / distance);
+                break;
+            }
+            remaining -= distance;
+            current = target;
+            progress.advance(movement.mode);
+        }
+        linvel.0 = (current - position.0) / delta;
+    }
+}
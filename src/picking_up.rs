@@ -1,25 +1,62 @@
 use avian2d::prelude::*;
+use bevy::color::palettes::css;
+use bevy::ecs::system::SystemParam;
 use bevy::platform::collections::HashMap;
 use bevy::prelude::*;
 use bevy_enhanced_input::prelude::*;
 
+use crate::During;
 use crate::camera::CameraTarget;
+use crate::force_field::InForceField;
 use crate::player::PlayerFacing;
+use crate::topple_detection::{LaidFlat, StartingRotation};
 
 pub struct PickingUpPlugin;
 
 #[derive(Component)]
 pub struct Pickable {
-    pub hold_at_offset: Vec2,
+    /// Half the size of the object when it's upright.
+    pub half_extents: Vec2,
+}
+
+impl Pickable {
+    fn half_extents(&self, flat: bool) -> Vec2 {
+        if flat {
+            self.half_extents.yx()
+        } else {
+            self.half_extents
+        }
+    }
+
+    /// From the object's center to the bottom it's held and placed at.
+    fn hold_at_offset(&self, flat: bool) -> Vec2 {
+        -self.half_extents(flat).y * Vec2::Y
+    }
 }
 
 #[derive(InputAction, Debug)]
 #[input_action(output = bool)]
 pub struct PlayerPickUp;
 
+/// Triggered on the picker entity when it should pick up (or place) a [`Pickable`].
+#[derive(Event)]
+pub struct PickUpRequested;
+
+#[derive(InputAction, Debug)]
+#[input_action(output = bool)]
+pub struct PlayerRotateHeld;
+
+/// Triggered on the picker entity when it should turn the [`Pickable`] it carries between upright
+/// and lying flat.
+#[derive(Event)]
+pub struct RotateHeldRequested;
+
 impl Plugin for PickingUpPlugin {
     fn build(&self, app: &mut App) {
         app.add_observer(initiate_pick_up);
+        app.add_observer(rotate_held_object);
+        app.init_resource::<PlacementPreview>();
+        app.add_systems(Update, show_placement_preview.in_set(During::Gameplay));
         app.add_systems(
             FixedUpdate,
             (
@@ -33,32 +70,145 @@ impl Plugin for PickingUpPlugin {
     }
 }
 
-#[derive(Default, Debug, Component)]
+#[derive(Default, Debug, Clone, Component)]
 pub struct Picker {
     holding: Option<Entity>,
     pub immobilized: bool,
+    /// Whether the held object is turned on its side.
+    pub flat: bool,
 }
 
 impl Picker {
-    fn clear(&mut self) {
+    pub fn clear(&mut self) {
         *self = Default::default();
     }
+
+    pub fn holding(&self) -> Option<Entity> {
+        self.holding
+    }
 }
 
 pub const PICKER_OFFSET: Vec2 = Vec2::new(0.0, 3.0);
 
-#[derive(Debug, Component)]
-pub struct HeldBy(Entity);
+/// How far in front of the picker the near side of a placed object is.
+const PLACE_GAP: f32 = 1.4;
+/// How far below the picker a held object can be set down.
+const MAX_PLACE_DEPTH: f32 = 3.0;
+/// Placed objects are let go this far above the surface they are placed on.
+const PLACE_CLEARANCE: f32 = 0.05;
 
-#[derive(Debug, Component)]
+fn carry_rotation(flat: bool) -> Rotation {
+    if flat {
+        Rotation::degrees(90.0)
+    } else {
+        Rotation::IDENTITY
+    }
+}
+
+#[derive(SystemParam)]
+struct Placement<'w, 's> {
+    spatial_query: Res<'w, SpatialQueryPipeline>,
+    sensors_query: Query<'w, 's, (), With<Sensor>>,
+}
+
+impl Placement<'_, '_> {
+    /// Where the center of a held object ends up when it gets placed in `direction` - on top of
+    /// whatever is at that spot, so that objects can be stacked or put on ledges.
+    ///
+    /// `filter` should exclude the picker and the held object.
+    fn position(
+        &self,
+        picker_position: Vec2,
+        direction: Dir2,
+        pickable: &Pickable,
+        flat: bool,
+        filter: &SpatialQueryFilter,
+    ) -> Vec2 {
+        let half_extents = pickable.half_extents(flat);
+        let x = (picker_position + (PLACE_GAP + half_extents.x) * *direction).x;
+        let cast_from = Vec2::new(x, picker_position.y + PICKER_OFFSET.y);
+        let bottom_y = self
+            .spatial_query
+            .cast_shape_predicate(
+                // Narrower than the object, so that the neighbors it'd just touch don't count -
+                // but no narrower than its middle half, because the thinnest bricks are only 0.1
+                // wide.
+                &Collider::rectangle((2.0 * half_extents.x - 0.1).max(half_extents.x), 0.0),
+                cast_from,
+                0.0,
+                Dir2::NEG_Y,
+                &ShapeCastConfig {
+                    max_distance: PICKER_OFFSET.y + MAX_PLACE_DEPTH,
+                    ..Default::default()
+                },
+                filter,
+                &|entity| !self.sensors_query.contains(entity),
+            )
+            .map_or(
+                // Nothing to put it on - let go of it a bit below the picker and let it fall.
+                picker_position.y - 1.0,
+                |hit| cast_from.y - hit.distance + PLACE_CLEARANCE,
+            );
+        Vec2::new(x, bottom_y) - pickable.hold_at_offset(flat)
+    }
+
+    /// Whether a held object can't be moved from `from` to `position` and placed there - because
+    /// it'd overlap something solid there, or hit something on its way. [`HeldStatus::Placed`] moves
+    /// it straight toward `position`.
+    fn blocked(
+        &self,
+        from: Vec2,
+        position: Vec2,
+        pickable: &Pickable,
+        flat: bool,
+        filter: &SpatialQueryFilter,
+    ) -> bool {
+        // Shrunk a little, so that merely touching the floor or a neighbor does not count.
+        let size = 2.0 * pickable.half_extents(flat) - Vec2::splat(0.1);
+        let shape = Collider::rectangle(size.x, size.y);
+        let is_solid = |entity| !self.sensors_query.contains(entity);
+        if self
+            .spatial_query
+            .shape_intersections(&shape, position, 0.0, filter)
+            .into_iter()
+            .any(is_solid)
+        {
+            return true;
+        }
+        let Ok((direction, distance)) = Dir2::new_and_length(position - from) else {
+            return false;
+        };
+        self.spatial_query
+            .cast_shape_predicate(
+                &shape,
+                from,
+                0.0,
+                direction,
+                &ShapeCastConfig {
+                    max_distance: distance,
+                    ..Default::default()
+                },
+                filter,
+                &is_solid,
+            )
+            .is_some()
+    }
+}
+
+#[derive(Debug, Clone, Component)]
+pub struct HeldBy(pub Entity);
+
+#[derive(Debug, Clone, Component)]
 pub enum HeldStatus {
     Lifted,
     Carried,
     Placed(Dir2),
+    /// Released with this velocity on the next step.
+    Thrown(Vec2),
 }
 
 fn initiate_pick_up(
-    trigger: Trigger<Started<PlayerPickUp>>,
+    trigger: Trigger<PickUpRequested>,
     mut picker_query: Query<
         (&mut Picker, &Position, &PlayerFacing),
         // When we lose camera target that means the toppling has begun - and we no longer
@@ -66,8 +216,8 @@ fn initiate_pick_up(
         With<CameraTarget>,
     >,
     pickable_filter: Query<(), (With<Pickable>, Without<HeldStatus>)>,
-    mut held_query: Query<(&HeldBy, &mut HeldStatus), With<Pickable>>,
-    spatial_query: Res<SpatialQueryPipeline>,
+    mut held_query: Query<(&HeldBy, &mut HeldStatus, &Pickable, &Position)>,
+    placement: Placement,
     mut commands: Commands,
 ) {
     let picker_entity = trigger.target();
@@ -76,7 +226,9 @@ fn initiate_pick_up(
     };
     'place_held_object: {
         if let Some(held_entity) = picker.holding {
-            let Ok((held_by, mut held_status)) = held_query.get_mut(held_entity) else {
+            let Ok((held_by, mut held_status, pickable, held_position)) =
+                held_query.get_mut(held_entity)
+            else {
                 warn!(
                     "{picker_entity} should be holding {held_entity} - but its query returns nothing"
                 );
@@ -91,12 +243,24 @@ fn initiate_pick_up(
                 picker.clear();
                 break 'place_held_object;
             }
+            let filter = SpatialQueryFilter::from_excluded_entities([picker_entity, held_entity]);
+            let position = placement.position(
+                picker_position.0,
+                facing.direction_2d(),
+                pickable,
+                picker.flat,
+                &filter,
+            );
+            // Better to keep holding it than to have the hold break on the way down.
+            if placement.blocked(held_position.0, position, pickable, picker.flat, &filter) {
+                return;
+            }
             *held_status = HeldStatus::Placed(facing.direction_2d());
             picker.immobilized = true;
             return;
         }
     }
-    let Some(hit) = spatial_query.cast_shape_predicate(
+    let Some(hit) = placement.spatial_query.cast_shape_predicate(
         &Collider::rectangle(0.0, 0.5),
         picker_position.0,
         0.0,
@@ -113,10 +277,46 @@ fn initiate_pick_up(
     let pickable_entity = hit.entity;
     commands
         .entity(pickable_entity)
-        .insert((HeldBy(picker_entity), HeldStatus::Lifted));
+        .insert((
+            HeldBy(picker_entity),
+            HeldStatus::Lifted,
+            // Held objects are carried - and therefore placed - upright, unless the picker lays
+            // them flat.
+            StartingRotation(carry_rotation(false)),
+        ))
+        .remove::<LaidFlat>();
     *picker = Picker {
         holding: Some(pickable_entity),
         immobilized: true,
+        flat: false,
+    }
+}
+
+fn rotate_held_object(
+    trigger: Trigger<RotateHeldRequested>,
+    mut picker_query: Query<&mut Picker, With<CameraTarget>>,
+    held_query: Query<&HeldStatus>,
+    mut commands: Commands,
+) {
+    let Ok(mut picker) = picker_query.get_mut(trigger.target()) else {
+        return;
+    };
+    let Some(held_entity) = picker.holding else {
+        return;
+    };
+    // Turning it while it's being lifted or placed would throw off where it ends up.
+    if !matches!(held_query.get(held_entity), Ok(HeldStatus::Carried)) {
+        return;
+    }
+    picker.flat = !picker.flat;
+    // Also makes the topple detection consider it standing when it's placed flat - so it must not
+    // count toward the win condition, because it can't be toppled from there.
+    let mut cmd = commands.entity(held_entity);
+    cmd.insert(StartingRotation(carry_rotation(picker.flat)));
+    if picker.flat {
+        cmd.insert(LaidFlat);
+    } else {
+        cmd.remove::<LaidFlat>();
     }
 }
 
@@ -133,8 +333,10 @@ fn apply_forces_to_held_objects(
         &mut ExternalForce,
         &ComputedMass,
         &GravityScale,
+        Option<&InForceField>,
     )>,
     mut picker_query: Query<(&mut Picker, &Position, &LinearVelocity), Without<Pickable>>,
+    placement: Placement,
     mut commands: Commands,
     time: Res<Time>,
     gravity: Res<Gravity>,
@@ -151,10 +353,14 @@ fn apply_forces_to_held_objects(
         mut force,
         mass,
         gravity_scale,
+        in_force_field,
     ) in held_query.iter_mut()
     {
+        // Cancel the gravity, but not force fields - the picker needs to hold against them.
         let anti_gravity = -gravity.0 * gravity_scale.0 * mass.value();
-        force.set_force(anti_gravity);
+        let field_force =
+            in_force_field.map_or(Vec2::ZERO, |in_force_field| in_force_field.0 * mass.value());
+        force.set_force(anti_gravity + field_force);
         let Ok((mut picker, picker_position, picker_velocity)) =
             picker_query.get_mut(picker_entity)
         else {
@@ -165,12 +371,13 @@ fn apply_forces_to_held_objects(
         };
         let held_status = held_status.as_mut();
 
-        let angle_to_add = held_rotation.angle_between(Rotation::IDENTITY);
+        let angle_to_add = held_rotation.angle_between(carry_rotation(picker.flat));
         angvel.0 = angle_to_add / time.delta_secs();
+        let hold_at_offset = pickable.hold_at_offset(picker.flat);
 
         match held_status {
             HeldStatus::Lifted => {
-                let target_position = picker_position.0 + PICKER_OFFSET - pickable.hold_at_offset;
+                let target_position = picker_position.0 + PICKER_OFFSET - hold_at_offset;
                 let vec_to_target = target_position - held_position.0;
                 const LIFT_SPEED: f32 = 10.0;
                 let desired_velocity = if 0.5 < vec_to_target.y {
@@ -188,15 +395,20 @@ fn apply_forces_to_held_objects(
                 }
             }
             HeldStatus::Carried => {
-                let target_position = picker_position.0 + PICKER_OFFSET - pickable.hold_at_offset;
+                let target_position = picker_position.0 + PICKER_OFFSET - hold_at_offset;
                 let vec_to_target = target_position - held_position.0;
                 let desired_velocity = 0.5 * vec_to_target / time.delta_secs() + picker_velocity.0; //.clamp_length_max(40.0);
                 let desired_boost = desired_velocity - linvel.0;
                 force.apply_force(desired_boost / time.delta_secs());
             }
             HeldStatus::Placed(dir) => {
-                let target_position =
-                    picker_position.0 + 1.5 * **dir - pickable.hold_at_offset - 1.0 * Vec2::Y;
+                let target_position = placement.position(
+                    picker_position.0,
+                    *dir,
+                    pickable,
+                    picker.flat,
+                    &SpatialQueryFilter::from_excluded_entities([picker_entity, held_entity]),
+                );
                 let vec_to_target = target_position - held_position.0;
                 const LIFT_SPEED: f32 = 10.0;
                 let desired_velocity = if vec_to_target.dot(**dir) < 0.5 {
@@ -219,12 +431,100 @@ fn apply_forces_to_held_objects(
                     // picker.immobilized = false;
                 }
             }
+            HeldStatus::Thrown(velocity) => {
+                commands
+                    .entity(held_entity)
+                    .remove::<(HeldBy, HeldStatus)>();
+                linvel.0 = *velocity;
+                angvel.0 = 0.0;
+                force.clear();
+                picker.clear();
+            }
         }
     }
 }
 
+#[derive(Resource)]
+struct PlacementPreview {
+    entity: Entity,
+    fits_material: MeshMaterial3d<StandardMaterial>,
+    blocked_material: MeshMaterial3d<StandardMaterial>,
+}
+
+impl FromWorld for PlacementPreview {
+    fn from_world(world: &mut World) -> Self {
+        let mesh = Mesh3d(world.add_asset::<Mesh>(Cuboid::new(1.0, 1.0, 1.0)));
+        let fits_material = MeshMaterial3d(world.add_asset(StandardMaterial {
+            base_color: css::WHITE.with_alpha(0.3).into(),
+            alpha_mode: AlphaMode::Blend,
+            ..Default::default()
+        }));
+        let blocked_material = MeshMaterial3d(world.add_asset(StandardMaterial {
+            base_color: css::RED.with_alpha(0.4).into(),
+            alpha_mode: AlphaMode::Blend,
+            ..Default::default()
+        }));
+        let entity = world
+            .spawn((
+                mesh,
+                fits_material.clone(),
+                Transform::default(),
+                Visibility::Hidden,
+            ))
+            .id();
+        Self {
+            entity,
+            fits_material,
+            blocked_material,
+        }
+    }
+}
+
+fn show_placement_preview(
+    pickers_query: Query<(Entity, &Picker, &Position, &PlayerFacing), With<CameraTarget>>,
+    held_query: Query<(&Pickable, &HeldStatus, &Position)>,
+    placement: Placement,
+    preview: Res<PlacementPreview>,
+    mut preview_query: Query<(
+        &mut Transform,
+        &mut Visibility,
+        &mut MeshMaterial3d<StandardMaterial>,
+    )>,
+) {
+    let Ok((mut transform, mut visibility, mut material)) = preview_query.get_mut(preview.entity)
+    else {
+        return;
+    };
+    *visibility = Visibility::Hidden;
+    for (picker_entity, picker, picker_position, facing) in pickers_query.iter() {
+        let Some(held_entity) = picker.holding else {
+            continue;
+        };
+        let Ok((pickable, HeldStatus::Carried, held_position)) = held_query.get(held_entity) else {
+            continue;
+        };
+        let filter = SpatialQueryFilter::from_excluded_entities([picker_entity, held_entity]);
+        let position = placement.position(
+            picker_position.0,
+            facing.direction_2d(),
+            pickable,
+            picker.flat,
+            &filter,
+        );
+        let blocked = placement.blocked(held_position.0, position, pickable, picker.flat, &filter);
+        transform.translation = position.extend(0.0);
+        transform.scale = (2.0 * pickable.half_extents(picker.flat)).extend(1.0);
+        *material = if blocked {
+            preview.blocked_material.clone()
+        } else {
+            preview.fits_material.clone()
+        };
+        *visibility = Visibility::Inherited;
+    }
+}
+
 #[derive(Component)]
-struct InitialCollisions(HashMap<Entity, bool>);
+pub struct InitialCollisions(HashMap<Entity, bool>);
 
 impl InitialCollisions {
     fn initiate(
@@ -284,10 +584,10 @@ fn break_hold_when_pickable_collides_with_something(
             commands
                 .entity(pickable_entity)
                 .remove::<(HeldBy, HeldStatus)>();
-            if let Ok(mut picker) = picker_query.get_mut(held_by_entity) {
-                if picker.holding == Some(pickable_entity) {
-                    picker.clear();
-                }
+            if let Ok(mut picker) = picker_query.get_mut(held_by_entity)
+                && picker.holding == Some(pickable_entity)
+            {
+                picker.clear();
             }
         }
     }
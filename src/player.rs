@@ -6,9 +6,11 @@ use bevy_yoleck::prelude::*;
 use bevy_yoleck::vpeol::VpeolWillContainClickableChildren;
 use bevy_yoleck::vpeol_3d::Vpeol3dPosition;
 
-use crate::arena::calculate_lowest_y;
+use crate::arena::{FallOutRule, calculate_fall_out_rule};
 use crate::camera::CameraTarget;
+use crate::kicking::Kicker;
 use crate::picking_up::Picker;
+use crate::throwing::{ThrowAim, Thrower};
 use crate::{AppState, During, GameOverReason};
 
 pub struct PlayerPlugin;
@@ -17,7 +19,11 @@ impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app.add_yoleck_entity_type({
             YoleckEntityType::new("Player")
+                // Identifies the level - see `level_handling::level_id`.
+                .with_uuid()
                 .with::<Vpeol3dPosition>()
+                .with::<Kicker>()
+                .with::<Thrower>()
                 .insert_on_init(|| (IsPlayer, CameraTarget))
         });
         app.add_systems(YoleckSchedule::Populate, populate_player);
@@ -32,7 +38,7 @@ impl Plugin for PlayerPlugin {
         );
         app.add_systems(
             FixedUpdate,
-            calculate_lowest_y
+            calculate_fall_out_rule
                 .pipe(kill_player_when_they_fall)
                 .in_set(During::Gameplay),
         );
@@ -108,6 +114,7 @@ fn populate_player(
         cmd.insert(TnuaAnimatingState::<PlayerAnimationState>::default());
 
         cmd.insert(Picker::default());
+        cmd.insert(ThrowAim::default());
     });
 }
 
@@ -219,14 +226,13 @@ fn animate_player(
 }
 
 fn kill_player_when_they_fall(
-    lowest_y: In<Option<f32>>,
+    fall_out_rule: In<FallOutRule>,
     players_query: Query<&GlobalTransform, With<IsPlayer>>,
     mut app_state: ResMut<NextState<AppState>>,
     mut game_over_reason: ResMut<GameOverReason>,
 ) {
-    let Some(lowest_y) = *lowest_y else { return };
     for player_transform in players_query.iter() {
-        if player_transform.translation().y < lowest_y - 20.0 {
+        if fall_out_rule.has_fallen_out(player_transform.translation().truncate(), 20.0) {
             *game_over_reason = GameOverReason::PlayerFell;
             app_state.set(AppState::GameOver);
         }
@@ -2,11 +2,17 @@ use bevy::prelude::*;
 use bevy_enhanced_input::prelude::*;
 use bevy_tnua::prelude::*;
 use bevy_yoleck::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::During;
+use crate::arena::ConveyorVelocity;
 use crate::camera::CameraTarget;
-use crate::picking_up::{Picker, PlayerPickUp};
+use crate::kicking::{KickRequested, PlayerKick};
+use crate::picking_up::{
+    PickUpRequested, Picker, PlayerPickUp, PlayerRotateHeld, RotateHeldRequested,
+};
 use crate::player::{IsPlayer, PlayerFacing};
+use crate::throwing::{PlayerThrow, ThrowAim};
 
 #[derive(InputAction, Debug)]
 #[input_action(output = f32)]
@@ -24,21 +30,67 @@ pub struct PlayerControlsPlugin;
 impl Plugin for PlayerControlsPlugin {
     fn build(&self, app: &mut App) {
         app.add_input_context::<PlayerOnFoot>();
+        app.add_observer(queue_pick_up);
+        app.add_observer(queue_kick);
+        app.add_observer(queue_rotate_held);
         app.add_systems(YoleckSchedule::Populate, add_controls_to_player);
-        app.add_systems(
+        app.configure_sets(
             FixedUpdate,
-            apply_controls
-                .in_set(TnuaUserControlsSystemSet)
+            (PlayerInputSet::Read, PlayerInputSet::Apply)
+                .chain()
                 .in_set(During::Gameplay),
         );
+        app.add_systems(
+            FixedUpdate,
+            (
+                read_live_input.in_set(PlayerInputSet::Read),
+                (
+                    apply_controls.in_set(TnuaUserControlsSystemSet),
+                    request_pick_up,
+                    request_kick,
+                    request_rotate_held,
+                )
+                    .in_set(PlayerInputSet::Apply),
+            ),
+        );
     }
 }
 
+/// Systems that fill [`PlayerInput`] should run in [`PlayerInputSet::Read`] (or between it and
+/// [`PlayerInputSet::Apply`] if they want to override the live input).
+#[derive(SystemSet, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum PlayerInputSet {
+    Read,
+    Apply,
+}
+
+/// The controls the player gave during the current fixed tick.
+///
+/// This is read from the input device, but may be overridden - e.g. when playing back a replay.
+#[derive(Component, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Debug)]
+pub struct PlayerInput {
+    pub run: f32,
+    pub jump: bool,
+    pub pick_up: bool,
+    // Defaulted so that replays recorded before these actions were added still load.
+    #[serde(default)]
+    pub kick: bool,
+    #[serde(default)]
+    pub throw: bool,
+    #[serde(default)]
+    pub rotate_held: bool,
+    /// Rewinding is done from the menus, not with the player controls - but replays need to know
+    /// when it happened, so they set it on the first tick after the rewind.
+    #[serde(default)]
+    pub rewind: bool,
+}
+
 fn add_controls_to_player(mut populate: YoleckPopulate<(), With<IsPlayer>>) {
     populate.populate(|ctx, mut cmd, ()| {
         if ctx.is_in_editor() {
             return;
         }
+        cmd.insert((PlayerInput::default(), ConveyorVelocity::default()));
         let mut input_map = Actions::<PlayerOnFoot>::default();
 
         input_map.bind::<PlayerRun>().to((
@@ -62,21 +114,110 @@ fn add_controls_to_player(mut populate: YoleckPopulate<(), With<IsPlayer>>) {
             KeyCode::KeyK,
             GamepadButton::West,
         ));
+
+        input_map.bind::<PlayerKick>().to((
+            KeyCode::ShiftLeft,
+            KeyCode::ShiftRight,
+            KeyCode::KeyL,
+            GamepadButton::East,
+        ));
+
+        input_map.bind::<PlayerThrow>().to((
+            KeyCode::AltLeft,
+            KeyCode::AltRight,
+            KeyCode::KeyI,
+            GamepadButton::North,
+        ));
+
+        input_map.bind::<PlayerRotateHeld>().to((
+            KeyCode::KeyQ,
+            KeyCode::KeyU,
+            GamepadButton::RightTrigger,
+        ));
         cmd.insert(input_map);
     });
 }
 
-fn apply_controls(
+fn read_live_input(mut query: Query<(&Actions<PlayerOnFoot>, &mut PlayerInput)>) {
+    for (actions, mut input) in query.iter_mut() {
+        input.run = actions.value::<PlayerRun>().unwrap().as_axis1d();
+        input.jump = actions.state::<PlayerJump>().unwrap() == ActionState::Fired;
+        input.throw = actions.state::<PlayerThrow>().unwrap() == ActionState::Fired;
+    }
+}
+
+// Picking up, kicking and rotating the held object are edge-triggered, so we can't just sample
+// their state each tick like we do with the other actions.
+fn queue_pick_up(trigger: Trigger<Started<PlayerPickUp>>, mut query: Query<&mut PlayerInput>) {
+    if let Ok(mut input) = query.get_mut(trigger.target()) {
+        input.pick_up = true;
+    }
+}
+
+fn queue_kick(trigger: Trigger<Started<PlayerKick>>, mut query: Query<&mut PlayerInput>) {
+    if let Ok(mut input) = query.get_mut(trigger.target()) {
+        input.kick = true;
+    }
+}
+
+fn queue_rotate_held(
+    trigger: Trigger<Started<PlayerRotateHeld>>,
+    mut query: Query<&mut PlayerInput>,
+) {
+    if let Ok(mut input) = query.get_mut(trigger.target()) {
+        input.rotate_held = true;
+    }
+}
+
+fn request_pick_up(mut query: Query<(Entity, &mut PlayerInput)>, mut commands: Commands) {
+    for (entity, mut input) in query.iter_mut() {
+        if input.pick_up {
+            input.pick_up = false;
+            commands.trigger_targets(PickUpRequested, entity);
+        }
+    }
+}
+
+fn request_kick(mut query: Query<(Entity, &mut PlayerInput)>, mut commands: Commands) {
+    for (entity, mut input) in query.iter_mut() {
+        if input.kick {
+            input.kick = false;
+            commands.trigger_targets(KickRequested, entity);
+        }
+    }
+}
+
+fn request_rotate_held(mut query: Query<(Entity, &mut PlayerInput)>, mut commands: Commands) {
+    for (entity, mut input) in query.iter_mut() {
+        if input.rotate_held {
+            input.rotate_held = false;
+            commands.trigger_targets(RotateHeldRequested, entity);
+        }
+    }
+}
+
+pub fn apply_controls(
     // time: Res<Time>,
     mut query: Query<(
-        &Actions<PlayerOnFoot>,
+        &PlayerInput,
         &mut TnuaController,
         &mut PlayerFacing,
         &Picker,
+        &ThrowAim,
+        &ConveyorVelocity,
         Has<CameraTarget>,
     )>,
 ) {
-    for (input, mut controller, mut player_facing, picker, has_camera_target) in query.iter_mut() {
+    for (
+        input,
+        mut controller,
+        mut player_facing,
+        picker,
+        throw_aim,
+        conveyor_velocity,
+        has_camera_target,
+    ) in query.iter_mut()
+    {
         let controller = controller.as_mut();
         // When we lose camera target that means the toppling has begun - and we no longer
         // want to allow the player to move.
@@ -84,8 +225,10 @@ fn apply_controls(
             controller.neutralize_basis();
             continue;
         }
-        let x_input = input.value::<PlayerRun>().unwrap().as_axis1d();
-        let desired_velocity = Vec3::X * 20.0 * x_input;
+        // While aiming a throw the run controls move the aim instead of the player.
+        let x_input = if throw_aim.aiming { 0.0 } else { input.run };
+        // The conveyor carries the player along while they walk on it.
+        let desired_velocity = Vec3::X * 20.0 * x_input + conveyor_velocity.0.extend(0.0);
 
         if x_input <= -0.1 {
             *player_facing = PlayerFacing::Left;
@@ -98,7 +241,7 @@ fn apply_controls(
             cling_distance: 0.5,
             ..Default::default()
         });
-        if input.state::<PlayerJump>().unwrap() == ActionState::Fired {
+        if input.jump && !throw_aim.aiming {
             controller.action(TnuaBuiltinJump {
                 height: 5.0,
                 allow_in_air: false,
@@ -0,0 +1,244 @@
+use std::collections::BTreeMap;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use bevy_pkv::PkvStore;
+use serde::{Deserialize, Serialize};
+
+use crate::ghost::GhostTrajectory;
+use crate::level_handling::{LEVEL_RECORDS_PKV_KEY, LevelRecord};
+
+pub struct ProfilesPlugin;
+
+impl Plugin for ProfilesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ActiveProfile>();
+    }
+}
+
+/// Identify the game's [`PkvStore`], and the data directory exported profiles are written to.
+pub const PKV_ORGANIZATION: &str = "AeonFelis";
+pub const PKV_APPLICATION: &str = "TimeToTopple";
+
+pub const DEFAULT_PROFILE_NAME: &str = "Player";
+const PROFILES_PKV_KEY: &str = "profiles";
+const ACTIVE_PROFILE_PKV_KEY: &str = "active_profile";
+
+/// All the progress is saved in the [`PkvStore`] under keys prefixed by the active profile.
+#[derive(Resource, Debug)]
+pub struct ActiveProfile {
+    pub name: String,
+    pub all_profiles: Vec<String>,
+}
+
+impl FromWorld for ActiveProfile {
+    fn from_world(world: &mut World) -> Self {
+        let pkv = world.resource::<PkvStore>();
+        let mut all_profiles = pkv.get::<Vec<String>>(PROFILES_PKV_KEY).unwrap_or_default();
+        if all_profiles.is_empty() {
+            all_profiles.push(DEFAULT_PROFILE_NAME.to_owned());
+        }
+        let name = pkv
+            .get::<String>(ACTIVE_PROFILE_PKV_KEY)
+            .ok()
+            .filter(|name| all_profiles.contains(name))
+            .unwrap_or_else(|| all_profiles[0].clone());
+        Self { name, all_profiles }
+    }
+}
+
+impl ActiveProfile {
+    pub fn pkv_key(&self, key: &str) -> String {
+        profile_pkv_key(&self.name, key)
+    }
+
+    /// Creates the profile if it does not exist yet. The name must be [`is_valid_profile_name`].
+    pub fn switch_to(&mut self, name: &str, pkv: &mut PkvStore) {
+        if !self.all_profiles.iter().any(|profile| profile == name) {
+            self.all_profiles.push(name.to_owned());
+            if let Err(err) = pkv.set(PROFILES_PKV_KEY, &self.all_profiles) {
+                error!("Unable to save profile list: {}", err);
+            }
+        }
+        self.name = name.to_owned();
+        if let Err(err) = pkv.set_string(ACTIVE_PROFILE_PKV_KEY, &self.name) {
+            error!("Unable to save active profile: {}", err);
+        }
+    }
+
+    /// `name`, or - if a profile with that name already exists - `name` with a number appended.
+    pub fn unused_name(&self, name: &str) -> String {
+        let is_used =
+            |candidate: &str| self.all_profiles.iter().any(|profile| profile == candidate);
+        if !is_used(name) {
+            return name.to_owned();
+        }
+        (2..)
+            .map(|number| format!("{name} ({number})"))
+            .find(|candidate| !is_used(candidate))
+            .expect("there are fewer profiles than numbers")
+    }
+}
+
+fn profile_pkv_key(profile_name: &str, key: &str) -> String {
+    format!("profile/{profile_name}/{key}")
+}
+
+/// A `/` in the name would let the [`PkvStore`] keys of one profile alias those of another - e.g.
+/// `a/ghost` and `a`.
+pub fn is_valid_profile_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains('/')
+}
+
+/// The format profiles are exported to and imported from.
+#[derive(Serialize, Deserialize, Default)]
+pub struct ExportedProfile {
+    /// Exports from before it was added are named after their file.
+    #[serde(default)]
+    pub profile_name: Option<String>,
+    pub level_records: BTreeMap<String, LevelRecord>,
+    /// By the same level keys as `level_records`. Exports from before it was added have none.
+    #[serde(default)]
+    pub ghosts: BTreeMap<String, GhostTrajectory>,
+}
+
+impl ExportedProfile {
+    pub fn read_from_pkv(profile_name: &str, pkv: &PkvStore) -> Self {
+        let level_records: BTreeMap<String, LevelRecord> = pkv
+            .get(profile_pkv_key(profile_name, LEVEL_RECORDS_PKV_KEY))
+            .unwrap_or_default();
+        // Ghosts are only saved when completing a level, which always leaves a record.
+        let ghosts = level_records
+            .keys()
+            .filter_map(|level_key| {
+                let ghost = pkv
+                    .get(profile_pkv_key(
+                        profile_name,
+                        &GhostTrajectory::pkv_key(level_key),
+                    ))
+                    .ok()?;
+                Some((level_key.clone(), ghost))
+            })
+            .collect();
+        Self {
+            profile_name: Some(profile_name.to_owned()),
+            level_records,
+            ghosts,
+        }
+    }
+
+    pub fn write_to_pkv(&self, profile_name: &str, pkv: &mut PkvStore) {
+        if let Err(err) = pkv.set(
+            profile_pkv_key(profile_name, LEVEL_RECORDS_PKV_KEY),
+            &self.level_records,
+        ) {
+            error!(
+                "Unable to save imported profile {:?}: {}",
+                profile_name, err
+            );
+        }
+        for (level_key, ghost) in self.ghosts.iter() {
+            if let Err(err) = pkv.set(
+                profile_pkv_key(profile_name, &GhostTrajectory::pkv_key(level_key)),
+                ghost,
+            ) {
+                error!(
+                    "Unable to save the ghost of imported profile {:?}: {}",
+                    profile_name, err
+                );
+            }
+        }
+    }
+}
+
+/// In the user's data directory - next to the [`PkvStore`] - because the game's own directory may
+/// not be writable once it is installed.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn profiles_dir() -> PathBuf {
+    directories::ProjectDirs::from("", PKV_ORGANIZATION, PKV_APPLICATION)
+        .map_or_else(|| PathBuf::from("."), |dirs| dirs.data_dir().to_owned())
+        .join("profiles")
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ExportedProfile {
+    /// Profile names are typed by the user, so only the characters that are safe in file names on
+    /// all platforms are kept. Names that lose characters get a hash of the whole name appended, so
+    /// that e.g. `QA?1` and `QA_1` are not exported to the same file.
+    pub fn export_path(profile_name: &str) -> PathBuf {
+        let file_stem = profile_name
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || matches!(c, '-' | '_' | ' ') {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>();
+        let file_stem = file_stem.trim();
+        let file_stem = if file_stem == profile_name {
+            file_stem.to_owned()
+        } else {
+            format!(
+                "{} {:08x}",
+                if file_stem.is_empty() {
+                    "profile"
+                } else {
+                    file_stem
+                },
+                name_hash(profile_name),
+            )
+        };
+        profiles_dir().join(format!("{file_stem}.json"))
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::create_dir_all(profiles_dir())?;
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let file = std::io::BufReader::new(std::fs::File::open(path)?);
+        Ok(serde_json::from_reader(file)?)
+    }
+
+    /// The profile name of each file is the one it was exported from, or its stem if it has none.
+    /// This reads all the files, so it should not be called every frame.
+    pub fn list_importable() -> Vec<(String, PathBuf)> {
+        let Ok(dir) = std::fs::read_dir(profiles_dir()) else {
+            return Vec::new();
+        };
+        let mut result = dir
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                if path.extension()? != "json" {
+                    return None;
+                }
+                let profile_name = Self::load(&path)
+                    .ok()?
+                    .profile_name
+                    .filter(|profile_name| is_valid_profile_name(profile_name));
+                let profile_name = match profile_name {
+                    Some(profile_name) => profile_name,
+                    None => path.file_stem()?.to_str()?.to_owned(),
+                };
+                Some((profile_name, path))
+            })
+            .collect::<Vec<_>>();
+        result.sort();
+        result
+    }
+}
+
+/// FNV-1a, because the hasher of the standard library may change between Rust versions - which
+/// would export the same profile to a different file.
+#[cfg(not(target_arch = "wasm32"))]
+fn name_hash(name: &str) -> u32 {
+    name.bytes().fold(0x811c9dc5, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x01000193)
+    })
+}
@@ -0,0 +1,273 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use bevy::asset::io::file::FileAssetReader;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::level_handling::LevelProgress;
+use crate::player_controls::{PlayerInput, PlayerInputSet};
+use crate::rewind::{Rewind, ToppleSnapshot};
+use crate::{AppState, During};
+
+pub struct ReplayPlugin {
+    pub mode: ReplayMode,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ReplayMode {
+    Disabled,
+    /// Save the input of each attempt to a replay file in this directory.
+    Record(PathBuf),
+    /// Feed the input from the level's replay file in this directory instead of the live input.
+    Playback(PathBuf),
+    /// Feed the input from the level's solution file instead of the live input.
+    PlaySolution,
+}
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        if cfg!(target_arch = "wasm32") && self.mode != ReplayMode::Disabled {
+            warn!("Replays are read from and written to files, which is not supported on wasm");
+            return;
+        }
+        match &self.mode {
+            ReplayMode::Disabled => {}
+            ReplayMode::Record(dir) => {
+                app.insert_resource(ReplayFiles {
+                    dir: dir.clone(),
+                    extension: Replay::RECORDING_EXTENSION,
+                });
+                app.init_resource::<ReplayRecording>();
+                app.add_observer(record_rewind);
+                app.add_systems(
+                    OnEnter(AppState::LoadLevel),
+                    (save_recording, start_recording).chain(),
+                );
+                app.add_systems(OnEnter(AppState::LevelCompleted), save_recording);
+                app.add_systems(OnEnter(AppState::GameOver), save_recording);
+                app.add_systems(
+                    FixedUpdate,
+                    record_input
+                        .after(PlayerInputSet::Read)
+                        .before(PlayerInputSet::Apply)
+                        .in_set(During::Gameplay),
+                );
+            }
+            ReplayMode::Playback(_) | ReplayMode::PlaySolution => {
+                app.insert_resource(match &self.mode {
+                    ReplayMode::Playback(dir) => ReplayFiles {
+                        dir: dir.clone(),
+                        extension: Replay::RECORDING_EXTENSION,
+                    },
+                    _ => ReplayFiles {
+                        dir: Replay::solutions_dir(),
+                        extension: Replay::SOLUTION_EXTENSION,
+                    },
+                });
+                app.init_resource::<ReplayPlayback>();
+                app.add_systems(OnEnter(AppState::LoadLevel), start_playback);
+                // Before the fixed ticks, like a rewind from the menus.
+                app.add_systems(PreUpdate, play_back_rewind);
+                app.add_systems(
+                    FixedUpdate,
+                    play_back_input
+                        .after(PlayerInputSet::Read)
+                        .before(PlayerInputSet::Apply)
+                        .in_set(During::Gameplay),
+                );
+            }
+        }
+    }
+}
+
+/// The input the player gave in each fixed tick of an attempt, compressed into runs of identical
+/// input.
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct Replay {
+    pub segments: Vec<ReplaySegment>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReplaySegment {
+    pub ticks: u32,
+    pub input: PlayerInput,
+}
+
+impl Replay {
+    pub const RECORDING_EXTENSION: &str = "replay";
+    /// Solutions are recordings that were renamed by hand, so that recording new attempts will not
+    /// overwrite them.
+    pub const SOLUTION_EXTENSION: &str = "solution";
+
+    /// Solutions are shipped next to the levels they solve.
+    pub fn solutions_dir() -> PathBuf {
+        FileAssetReader::get_base_path()
+            .join("assets")
+            .join("levels")
+    }
+
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        serde_json::to_writer(BufWriter::new(File::create(path)?), self)?;
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    pub fn push(&mut self, input: PlayerInput) {
+        if let Some(last_segment) = self.segments.last_mut()
+            && last_segment.input == input
+        {
+            last_segment.ticks += 1;
+            return;
+        }
+        self.segments.push(ReplaySegment { ticks: 1, input });
+    }
+}
+
+/// Where the replay files of the current [`ReplayMode`] are.
+#[derive(Resource)]
+struct ReplayFiles {
+    dir: PathBuf,
+    extension: &'static str,
+}
+
+impl ReplayFiles {
+    fn path_for_level(&self, level_filename: &str) -> PathBuf {
+        let level_name = level_filename
+            .strip_suffix(".yol")
+            .unwrap_or(level_filename);
+        self.dir.join(format!("{level_name}.{}", self.extension))
+    }
+}
+
+#[derive(Resource, Default)]
+struct ReplayRecording {
+    level: Option<String>,
+    replay: Replay,
+    rewound: bool,
+}
+
+fn start_recording(level_progress: Res<LevelProgress>, mut recording: ResMut<ReplayRecording>) {
+    *recording = ReplayRecording {
+        level: level_progress.current_level.clone(),
+        ..Default::default()
+    };
+}
+
+fn record_rewind(_: Trigger<Rewind>, mut recording: ResMut<ReplayRecording>) {
+    recording.rewound = true;
+}
+
+fn record_input(query: Query<&PlayerInput>, mut recording: ResMut<ReplayRecording>) {
+    let rewind = std::mem::take(&mut recording.rewound);
+    for input in query.iter() {
+        recording.replay.push(PlayerInput { rewind, ..*input });
+    }
+}
+
+/// Does not clear the recording, because a game over can be rewound and continued.
+fn save_recording(files: Res<ReplayFiles>, recording: Res<ReplayRecording>) {
+    let ReplayRecording { level, replay, .. } = recording.as_ref();
+    let Some(level) = level else {
+        return;
+    };
+    if replay.is_empty() {
+        return;
+    }
+    if let Err(err) = std::fs::create_dir_all(&files.dir) {
+        error!("Unable to create {}: {}", files.dir.display(), err);
+        return;
+    }
+    let path = files.path_for_level(level);
+    match replay.save(&path) {
+        Ok(()) => info!("Saved replay to {}", path.display()),
+        Err(err) => error!("Unable to save replay to {}: {}", path.display(), err),
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct ReplayPlayback {
+    replay: Replay,
+    segment_index: usize,
+    ticks_into_segment: u32,
+}
+
+impl ReplayPlayback {
+    fn next_input(&mut self) -> Option<PlayerInput> {
+        loop {
+            let segment = self.replay.segments.get(self.segment_index)?;
+            if self.ticks_into_segment < segment.ticks {
+                self.ticks_into_segment += 1;
+                return Some(segment.input);
+            }
+            self.segment_index += 1;
+            self.ticks_into_segment = 0;
+        }
+    }
+
+    fn peek_input(&self) -> Option<PlayerInput> {
+        let mut ticks_into_segment = self.ticks_into_segment;
+        for segment in self.replay.segments.iter().skip(self.segment_index) {
+            if ticks_into_segment < segment.ticks {
+                return Some(segment.input);
+            }
+            ticks_into_segment = 0;
+        }
+        None
+    }
+
+    /// Whether the player rewound before the next tick of the replay.
+    pub fn rewinds_next(&self) -> bool {
+        self.peek_input().is_some_and(|input| input.rewind)
+    }
+}
+
+fn start_playback(
+    level_progress: Res<LevelProgress>,
+    files: Res<ReplayFiles>,
+    mut playback: ResMut<ReplayPlayback>,
+) {
+    *playback = Default::default();
+    let Some(level) = level_progress.current_level.as_ref() else {
+        return;
+    };
+    let path = files.path_for_level(level);
+    match Replay::load(&path) {
+        Ok(replay) => playback.replay = replay,
+        Err(err) => error!("Unable to load replay from {}: {}", path.display(), err),
+    }
+}
+
+fn play_back_input(mut query: Query<&mut PlayerInput>, mut playback: ResMut<ReplayPlayback>) {
+    for mut input in query.iter_mut() {
+        // Once the replay runs out, the player just stands still.
+        *input = playback.next_input().unwrap_or_default();
+    }
+}
+
+fn play_back_rewind(
+    playback: Res<ReplayPlayback>,
+    topple_snapshot: Res<ToppleSnapshot>,
+    app_state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut commands: Commands,
+) {
+    if !playback.rewinds_next() || !topple_snapshot.can_rewind() {
+        return;
+    }
+    match app_state.get() {
+        AppState::Game => {}
+        // Rewinding from the game over menu continues the attempt.
+        AppState::GameOver => next_state.set(AppState::Game),
+        _ => return,
+    }
+    commands.trigger(Rewind);
+}
@@ -0,0 +1,267 @@
+use std::time::Duration;
+
+use avian2d::prelude::*;
+use bevy::prelude::*;
+
+use crate::camera::CameraTarget;
+use crate::contraptions::TriggerState;
+use crate::force_field::{BaseGravityScale, InForceField};
+use crate::level_handling::LevelProgress;
+use crate::moving_platform::PlatformProgress;
+use crate::picking_up::{HeldBy, HeldStatus, InitialCollisions, Picker};
+use crate::player::IsPlayer;
+use crate::topple_detection::{LaidFlat, StartingRotation, Toppleable, ToppledOutOfOrder};
+use crate::{AppState, During};
+
+pub struct RewindPlugin;
+
+impl Plugin for RewindPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ToppleSnapshot>();
+        app.add_systems(OnEnter(AppState::LoadLevel), ToppleSnapshot::reset);
+        app.add_systems(FixedUpdate, take_snapshot.in_set(During::Gameplay));
+        app.add_observer(rewind_to_snapshot);
+    }
+}
+
+/// Rewind to the last moment before the toppling started. Only trigger it when
+/// [`ToppleSnapshot::can_rewind`], because replays record every rewind.
+#[derive(Event)]
+pub struct Rewind;
+
+/// The state of all the bodies, updated every tick until the toppling starts.
+#[derive(Resource, Default)]
+pub struct ToppleSnapshot {
+    bodies: Vec<BodySnapshot>,
+    pickers: Vec<(Entity, Picker)>,
+    triggers: Vec<(Entity, TriggerState)>,
+    /// The play after the snapshot is discarded, so it should not count toward the best time.
+    attempt_time: Duration,
+    toppling_started: bool,
+    /// Increased every time the snapshot is taken.
+    generation: u64,
+}
+
+struct BodySnapshot {
+    entity: Entity,
+    position: Position,
+    rotation: Rotation,
+    linvel: LinearVelocity,
+    angvel: AngularVelocity,
+    toppleable: Option<Toppleable>,
+    // Picking up changes it, so it needs to be restored too.
+    starting_rotation: Option<Rotation>,
+    laid_flat: bool,
+    held: Option<(HeldBy, HeldStatus)>,
+    platform_progress: Option<PlatformProgress>,
+    forces: ForcesSnapshot,
+}
+
+/// Force fields and holding change these, and the changes are only undone when they end.
+#[derive(Default)]
+struct ForcesSnapshot {
+    gravity_scale: Option<GravityScale>,
+    base_gravity_scale: Option<BaseGravityScale>,
+    in_force_field: Option<InForceField>,
+    external_force: Option<ExternalForce>,
+}
+
+impl ToppleSnapshot {
+    fn reset(mut snapshot: ResMut<Self>) {
+        *snapshot = Default::default();
+    }
+
+    /// There is nothing to rewind to if the bricks never settled since the level started.
+    pub fn can_rewind(&self) -> bool {
+        self.toppling_started && 0 < self.generation
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+}
+
+/// A brick that's being pushed starts tilting a few ticks before it counts as falling. Taking the
+/// snapshot only while all the bricks turn this slowly means rewinding won't restore it mid-push.
+/// This is not measured as tilt, because leaning bricks may settle a bit off their starting
+/// rotation.
+const SETTLED_ANGULAR_SPEED: f32 = 0.05;
+
+pub fn take_snapshot(
+    mut snapshot: ResMut<ToppleSnapshot>,
+    bodies_query: Query<(
+        Entity,
+        &Position,
+        &Rotation,
+        &LinearVelocity,
+        &AngularVelocity,
+        Option<&Toppleable>,
+        Option<(&HeldBy, &HeldStatus)>,
+        Option<&PlatformProgress>,
+    )>,
+    starting_rotations_query: Query<&StartingRotation>,
+    laid_flat_query: Query<(), With<LaidFlat>>,
+    forces_query: Query<(
+        Option<&GravityScale>,
+        Option<&BaseGravityScale>,
+        Option<&InForceField>,
+        Option<&ExternalForce>,
+    )>,
+    pickers_query: Query<(Entity, &Picker)>,
+    triggers_query: Query<(Entity, &TriggerState)>,
+    level_progress: Res<LevelProgress>,
+) {
+    if snapshot.toppling_started {
+        return;
+    }
+    let mut any_turning = false;
+    for (entity, _, _, _, angvel, toppleable, held, _) in bodies_query.iter() {
+        // Bricks laid flat never count as falling, and may keep sliding long after being placed.
+        if laid_flat_query.contains(entity) {
+            continue;
+        }
+        match toppleable {
+            Some(Toppleable::Standing)
+                if held.is_none() && SETTLED_ANGULAR_SPEED < angvel.0.abs() =>
+            {
+                any_turning = true;
+            }
+            None | Some(Toppleable::Standing) => {}
+            Some(_) => {
+                // Only mark it - don't clear the snapshot, because we want to be able to rewind
+                // to it.
+                snapshot.toppling_started = true;
+                return;
+            }
+        }
+    }
+    if any_turning {
+        return;
+    }
+    snapshot.bodies = bodies_query
+        .iter()
+        .map(
+            |(entity, position, rotation, linvel, angvel, toppleable, held, platform_progress)| {
+                BodySnapshot {
+                    entity,
+                    position: *position,
+                    rotation: *rotation,
+                    linvel: *linvel,
+                    angvel: *angvel,
+                    toppleable: toppleable.cloned(),
+                    starting_rotation: starting_rotations_query
+                        .get(entity)
+                        .ok()
+                        .map(|starting_rotation| starting_rotation.0),
+                    laid_flat: laid_flat_query.contains(entity),
+                    held: held.map(|(held_by, held_status)| (held_by.clone(), held_status.clone())),
+                    platform_progress: platform_progress.cloned(),
+                    forces: forces_query
+                        .get(entity)
+                        .map(
+                            |(
+                                gravity_scale,
+                                base_gravity_scale,
+                                in_force_field,
+                                external_force,
+                            )| {
+                                ForcesSnapshot {
+                                    gravity_scale: gravity_scale.copied(),
+                                    base_gravity_scale: base_gravity_scale.cloned(),
+                                    in_force_field: in_force_field.cloned(),
+                                    external_force: external_force.copied(),
+                                }
+                            },
+                        )
+                        .unwrap_or_default(),
+                }
+            },
+        )
+        .collect();
+    snapshot.pickers = pickers_query
+        .iter()
+        .map(|(entity, picker)| (entity, picker.clone()))
+        .collect();
+    snapshot.triggers = triggers_query
+        .iter()
+        .map(|(entity, state)| (entity, state.clone()))
+        .collect();
+    snapshot.attempt_time = level_progress.attempt_time;
+    snapshot.generation += 1;
+}
+
+fn rewind_to_snapshot(
+    _: Trigger<Rewind>,
+    mut snapshot: ResMut<ToppleSnapshot>,
+    camera_target_query: Query<Entity, With<CameraTarget>>,
+    players_query: Query<Entity, With<IsPlayer>>,
+    mut level_progress: ResMut<LevelProgress>,
+    mut commands: Commands,
+) {
+    if !snapshot.can_rewind() {
+        return;
+    }
+    for body in snapshot.bodies.iter() {
+        let Ok(mut cmd) = commands.get_entity(body.entity) else {
+            continue;
+        };
+        cmd.insert((body.position, body.rotation, body.linvel, body.angvel));
+        if let Some(toppleable) = body.toppleable.as_ref() {
+            cmd.insert(toppleable.clone());
+        }
+        if let Some(starting_rotation) = body.starting_rotation {
+            cmd.insert(StartingRotation(starting_rotation));
+        }
+        if body.laid_flat {
+            cmd.insert(LaidFlat);
+        } else {
+            cmd.remove::<LaidFlat>();
+        }
+        cmd.remove::<(InitialCollisions, ToppledOutOfOrder)>();
+        if let Some((held_by, held_status)) = body.held.as_ref() {
+            cmd.insert((held_by.clone(), held_status.clone()));
+        } else {
+            cmd.remove::<(HeldBy, HeldStatus)>();
+        }
+        if let Some(platform_progress) = body.platform_progress.as_ref() {
+            cmd.insert(platform_progress.clone());
+        }
+        let forces = &body.forces;
+        match forces.gravity_scale {
+            Some(gravity_scale) => cmd.insert(gravity_scale),
+            None => cmd.remove::<GravityScale>(),
+        };
+        match forces.base_gravity_scale.as_ref() {
+            Some(base_gravity_scale) => cmd.insert(base_gravity_scale.clone()),
+            None => cmd.remove::<BaseGravityScale>(),
+        };
+        match forces.in_force_field.as_ref() {
+            Some(in_force_field) => cmd.insert(in_force_field.clone()),
+            None => cmd.remove::<InForceField>(),
+        };
+        if let Some(external_force) = forces.external_force {
+            cmd.insert(external_force);
+        }
+    }
+    for (entity, picker) in snapshot.pickers.iter() {
+        if let Ok(mut cmd) = commands.get_entity(*entity) {
+            cmd.insert(picker.clone());
+        }
+    }
+    for (entity, state) in snapshot.triggers.iter() {
+        if let Ok(mut cmd) = commands.get_entity(*entity) {
+            cmd.insert(state.clone());
+        }
+    }
+    level_progress.attempt_time = snapshot.attempt_time;
+
+    // The camera follows the toppling - but now the player is back in control.
+    for entity in camera_target_query.iter() {
+        commands.entity(entity).remove::<CameraTarget>();
+    }
+    for entity in players_query.iter() {
+        commands.entity(entity).insert(CameraTarget);
+    }
+
+    snapshot.toppling_started = false;
+}
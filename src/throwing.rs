@@ -0,0 +1,228 @@
+use std::f32::consts::{FRAC_PI_4, PI};
+
+use avian2d::prelude::*;
+use bevy::color::palettes::css;
+use bevy::prelude::*;
+use bevy_enhanced_input::prelude::*;
+use bevy_yoleck::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::camera::CameraTarget;
+use crate::force_field::InForceField;
+use crate::picking_up::{HeldStatus, Picker};
+use crate::player::{IsPlayer, PlayerFacing};
+use crate::player_controls::{PlayerInput, PlayerInputSet, apply_controls};
+use crate::{AppState, During};
+
+pub struct ThrowingPlugin;
+
+impl Plugin for ThrowingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TrajectoryDots>();
+        app.add_systems(
+            FixedUpdate,
+            aim_and_throw
+                // The controls read the aim, to keep the player in place while aiming.
+                .before(apply_controls)
+                .in_set(PlayerInputSet::Apply),
+        );
+        app.add_systems(Update, show_trajectory.in_set(During::Gameplay));
+        // `show_trajectory` does not run outside the gameplay, so it can't hide the dots there.
+        app.add_systems(OnExit(AppState::Game), hide_trajectory);
+        app.add_yoleck_edit_system(edit_thrower);
+    }
+}
+
+#[derive(InputAction, Debug)]
+#[input_action(output = bool)]
+pub struct PlayerThrow;
+
+/// The speed is the same for every throw - only the angle is aimed - so levels that need a
+/// farther or a shorter throw should change it.
+#[derive(Component, YoleckComponent, Serialize, Deserialize, PartialEq, Clone)]
+pub struct Thrower {
+    pub speed: f32,
+}
+
+impl Default for Thrower {
+    fn default() -> Self {
+        Self { speed: 15.0 }
+    }
+}
+
+const MIN_THROW_ANGLE: f32 = -PI / 9.0;
+const MAX_THROW_ANGLE: f32 = 4.0 * PI / 9.0;
+/// Radians per second.
+const AIM_SPEED: f32 = 1.5;
+
+/// Holding the throw button while carrying a brick aims (with the run controls), and releasing it
+/// throws the brick.
+#[derive(Component, Debug)]
+pub struct ThrowAim {
+    pub aiming: bool,
+    /// Above the horizontal, in the direction the thrower is facing.
+    pub angle: f32,
+}
+
+impl Default for ThrowAim {
+    fn default() -> Self {
+        Self {
+            aiming: false,
+            angle: FRAC_PI_4,
+        }
+    }
+}
+
+impl ThrowAim {
+    pub fn velocity(&self, thrower: &Thrower, facing: &PlayerFacing) -> Vec2 {
+        let direction = Vec2::from_angle(self.angle);
+        thrower.speed * Vec2::new(direction.x * facing.direction_2d().x, direction.y)
+    }
+}
+
+fn edit_thrower(mut ui: ResMut<YoleckUi>, mut edit: YoleckEdit<&mut Thrower, With<IsPlayer>>) {
+    let Ok(mut thrower) = edit.single_mut() else {
+        return;
+    };
+    ui.add(egui::Slider::new(&mut thrower.speed, 1.0..=50.0).text("Throw Speed"));
+}
+
+fn aim_and_throw(
+    time: Res<Time>,
+    mut throwers_query: Query<
+        (
+            &PlayerInput,
+            &mut ThrowAim,
+            &Thrower,
+            &Picker,
+            &PlayerFacing,
+        ),
+        With<CameraTarget>,
+    >,
+    mut held_query: Query<&mut HeldStatus>,
+) {
+    for (input, mut aim, thrower, picker, facing) in throwers_query.iter_mut() {
+        let Some(held_entity) = picker
+            .holding()
+            .filter(|held_entity| matches!(held_query.get(*held_entity), Ok(HeldStatus::Carried)))
+        else {
+            aim.aiming = false;
+            continue;
+        };
+        if input.throw {
+            aim.aiming = true;
+            // Pushing forward flattens the throw, pushing backward raises it.
+            let forward = input.run * facing.direction_2d().x;
+            aim.angle = (aim.angle - forward * AIM_SPEED * time.delta_secs())
+                .clamp(MIN_THROW_ANGLE, MAX_THROW_ANGLE);
+        } else if aim.aiming {
+            aim.aiming = false;
+            if let Ok(mut held_status) = held_query.get_mut(held_entity) {
+                *held_status = HeldStatus::Thrown(aim.velocity(thrower, facing));
+            }
+        }
+    }
+}
+
+const TRAJECTORY_DOTS: usize = 30;
+/// Seconds of flight between two dots.
+const TRAJECTORY_DOT_INTERVAL: f32 = 0.06;
+
+#[derive(Resource)]
+struct TrajectoryDots(Vec<Entity>);
+
+impl FromWorld for TrajectoryDots {
+    fn from_world(world: &mut World) -> Self {
+        let mesh = Mesh3d(world.add_asset::<Mesh>(Sphere::new(0.1)));
+        let material = MeshMaterial3d(world.add_asset::<StandardMaterial>(StandardMaterial {
+            base_color: css::WHITE.with_alpha(0.7).into(),
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..Default::default()
+        }));
+        Self(
+            (0..TRAJECTORY_DOTS)
+                .map(|_| {
+                    world
+                        .spawn((
+                            mesh.clone(),
+                            material.clone(),
+                            Transform::default(),
+                            Visibility::Hidden,
+                        ))
+                        .id()
+                })
+                .collect(),
+        )
+    }
+}
+
+fn show_trajectory(
+    throwers_query: Query<(Entity, &ThrowAim, &Thrower, &Picker, &PlayerFacing)>,
+    held_query: Query<(&Position, Option<&GravityScale>, Option<&InForceField>)>,
+    sensors_query: Query<(), With<Sensor>>,
+    gravity: Res<Gravity>,
+    spatial_query: Res<SpatialQueryPipeline>,
+    dots: Res<TrajectoryDots>,
+    mut dots_query: Query<(&mut Transform, &mut Visibility)>,
+) {
+    let mut points = Vec::new();
+    for (thrower_entity, aim, thrower, picker, facing) in throwers_query.iter() {
+        if !aim.aiming {
+            continue;
+        }
+        let Some(held_entity) = picker.holding() else {
+            continue;
+        };
+        let Ok((position, gravity_scale, in_force_field)) = held_query.get(held_entity) else {
+            continue;
+        };
+        let acceleration = gravity.0 * gravity_scale.map_or(1.0, |gravity_scale| gravity_scale.0)
+            + in_force_field.map_or(Vec2::ZERO, |in_force_field| in_force_field.0);
+        let velocity = aim.velocity(thrower, facing);
+        let filter = SpatialQueryFilter::from_excluded_entities([thrower_entity, held_entity]);
+
+        let mut previous = position.0;
+        points.push(previous);
+        for i in 1..TRAJECTORY_DOTS {
+            let t = i as f32 * TRAJECTORY_DOT_INTERVAL;
+            let point = position.0 + velocity * t + 0.5 * acceleration * t * t;
+            let Ok((direction, distance)) = Dir2::new_and_length(point - previous) else {
+                continue;
+            };
+            if let Some(hit) = spatial_query.cast_ray_predicate(
+                previous,
+                direction,
+                distance,
+                true,
+                &filter,
+                &|entity| !sensors_query.contains(entity),
+            ) {
+                points.push(previous + hit.distance * *direction);
+                break;
+            }
+            points.push(point);
+            previous = point;
+        }
+    }
+
+    for (i, dot) in dots.0.iter().enumerate() {
+        let Ok((mut transform, mut visibility)) = dots_query.get_mut(*dot) else {
+            continue;
+        };
+        if let Some(point) = points.get(i) {
+            transform.translation = point.extend(0.0);
+            *visibility = Visibility::Inherited;
+        } else {
+            *visibility = Visibility::Hidden;
+        }
+    }
+}
+
+fn hide_trajectory(dots: Res<TrajectoryDots>, mut dots_query: Query<&mut Visibility>) {
+    for dot in dots.0.iter() {
+        if let Ok(mut visibility) = dots_query.get_mut(*dot) {
+            *visibility = Visibility::Hidden;
+        }
+    }
+}
@@ -1,27 +1,33 @@
+use std::time::Duration;
+
 use avian2d::prelude::*;
 use bevy::prelude::*;
 
-use crate::arena::calculate_lowest_y;
+use crate::arena::{FallOutRule, calculate_fall_out_rule};
 use crate::camera::CameraTarget;
+use crate::picking_up::HeldBy;
+use crate::utils::is_in_transformed_unit_square;
+use crate::win_condition::{GoalHit, IsGoalRegion, WinCondition};
 use crate::{AppState, During, GameOverReason};
 
 pub struct ToppleDetectionPlugin;
 
 impl Plugin for ToppleDetectionPlugin {
     fn build(&self, app: &mut App) {
+        app.init_resource::<ToppleClock>();
         app.add_systems(
             FixedUpdate,
             (
                 update_toppleable,
                 detect_finish,
-                calculate_lowest_y.pipe(detect_toppleables_who_fell_out),
+                calculate_fall_out_rule.pipe(detect_toppleables_who_fell_out),
             )
                 .in_set(During::Gameplay),
         );
     }
 }
 
-#[derive(Debug, Component)]
+#[derive(Debug, Clone, Component)]
 pub enum Toppleable {
     Standing,
     Falling { immobile_timer: Timer },
@@ -29,22 +35,82 @@ pub enum Toppleable {
     FellOut,
 }
 
+/// How long it's been since the first [`Toppleable`] started falling.
+#[derive(Resource, Default)]
+struct ToppleClock(Duration);
+
+/// A [`Toppleable`] that ends the attempt if it gets knocked over, instead of having to be.
+#[derive(Debug, Component)]
+pub struct MustStayStanding;
+
+/// [`Toppleable`]s with a sequence number must start falling in the order of their numbers.
+#[derive(Debug, Component)]
+pub struct SequenceNumber(pub u32);
+
+/// Marks a [`Toppleable`] that started falling while one with a lower [`SequenceNumber`] was still
+/// standing.
+#[derive(Debug, Component)]
+pub struct ToppledOutOfOrder;
+
+/// A [`Toppleable`] the player laid flat. It has nothing to topple from, so it does not count
+/// toward the win condition - even though its [`StartingRotation`] makes it [`Toppleable::Standing`].
+#[derive(Debug, Component)]
+pub struct LaidFlat;
+
+/// The orientation a [`Toppleable`] is considered standing at. Defaults to upright.
+#[derive(Debug, Component)]
+pub struct StartingRotation(pub Rotation);
+
+/// The sine of how much the toppleable is tilted from its starting rotation.
+fn tilt(rotation: &Rotation, starting_rotation: Option<&StartingRotation>) -> f32 {
+    let relative = match starting_rotation {
+        Some(starting_rotation) => starting_rotation.0.inverse() * *rotation,
+        None => *rotation,
+    };
+    relative.sin.abs()
+}
+
 fn update_toppleable(
     time: Res<Time>,
     mut query: Query<(
         Entity,
         &mut Toppleable,
         &Rotation,
+        Option<&StartingRotation>,
         &LinearVelocity,
         &AngularVelocity,
+        Option<&SequenceNumber>,
+        Has<HeldBy>,
+        Has<LaidFlat>,
     )>,
     camera_target_query: Query<Entity, With<CameraTarget>>,
     mut commands: Commands,
 ) {
-    for (toppleable_entity, mut toppleable, rotation, linvel, angvel) in query.iter_mut() {
+    let mut newly_falling = Vec::new();
+    for (
+        toppleable_entity,
+        mut toppleable,
+        rotation,
+        starting_rotation,
+        linvel,
+        angvel,
+        sequence_number,
+        held,
+        laid_flat,
+    ) in query.iter_mut()
+    {
+        // Held toppleables get rotated upright, which is not toppling. Toppleables laid flat have
+        // nothing to topple from, so tilting them - e.g. by leaning them as a ramp - is not
+        // toppling either.
+        if held || laid_flat {
+            continue;
+        }
         match toppleable.as_mut() {
             Toppleable::Standing => {
-                if 0.1 < rotation.sin.abs() {
+                if 0.1 < tilt(rotation, starting_rotation) {
+                    if let Some(SequenceNumber(number)) = sequence_number {
+                        newly_falling.push((toppleable_entity, *number));
+                    }
                     *toppleable = Toppleable::Falling {
                         immobile_timer: Timer::from_seconds(1.0, TimerMode::Once),
                     };
@@ -64,29 +130,58 @@ fn update_toppleable(
             Toppleable::Stopped | Toppleable::FellOut => {}
         }
     }
+
+    // Checked after updating all of them, so that bricks falling on the same tick are not out of
+    // order with each other.
+    let Some(lowest_standing) = query
+        .iter()
+        .filter_map(|(_, toppleable, _, _, _, _, sequence_number, _, _)| {
+            matches!(toppleable, Toppleable::Standing).then_some(sequence_number?.0)
+        })
+        .min()
+    else {
+        return;
+    };
+    for (entity, number) in newly_falling {
+        if lowest_standing < number {
+            commands.entity(entity).insert(ToppledOutOfOrder);
+        }
+    }
 }
 
 fn detect_toppleables_who_fell_out(
-    lowest_y: In<Option<f32>>,
+    fall_out_rule: In<FallOutRule>,
     mut query: Query<(&mut Toppleable, &Position)>,
 ) {
-    let Some(lowest_y) = *lowest_y else { return };
     for (mut toppleable, position) in query.iter_mut() {
-        if position.y < lowest_y {
+        if fall_out_rule.has_fallen_out(position.0, 0.0) {
             *toppleable = Toppleable::FellOut;
         }
     }
 }
 
 fn detect_finish(
-    query: Query<(Entity, &Toppleable)>,
+    query: Query<(
+        Entity,
+        &Toppleable,
+        Has<MustStayStanding>,
+        Option<&SequenceNumber>,
+        Has<ToppledOutOfOrder>,
+        &Position,
+        Has<LaidFlat>,
+    )>,
+    win_condition_query: Query<&WinCondition>,
+    goal_regions_query: Query<&GlobalTransform, With<IsGoalRegion>>,
+    goals_hit_query: Query<(), With<GoalHit>>,
+    time: Res<Time>,
+    mut topple_clock: ResMut<ToppleClock>,
     camera_target_query: Query<Entity, With<CameraTarget>>,
     mut commands: Commands,
     mut app_state: ResMut<NextState<AppState>>,
     mut game_over_reason: ResMut<GameOverReason>,
 ) {
     let mut status = [0; 4];
-    for (_, toppleable) in query.iter() {
+    for (_, toppleable, _, _, _, _, _) in query.iter() {
         status[match toppleable {
             Toppleable::Standing => 0,
             Toppleable::Falling { .. } => 1,
@@ -95,36 +190,161 @@ fn detect_finish(
         }] += 1;
     }
 
+    for (entity, toppleable, must_stay_standing, sequence_number, toppled_out_of_order, _, _) in
+        query.iter()
+    {
+        let reason = if must_stay_standing && !matches!(toppleable, Toppleable::Standing) {
+            GameOverReason::ProtectedBrickToppled
+        } else if toppled_out_of_order && let Some(SequenceNumber(number)) = sequence_number {
+            GameOverReason::ToppledOutOfOrder(*number)
+        } else {
+            continue;
+        };
+        for entity in camera_target_query.iter() {
+            commands.entity(entity).remove::<CameraTarget>();
+        }
+        commands.entity(entity).insert(CameraTarget);
+        *game_over_reason = reason;
+        app_state.set(AppState::GameOver);
+        return;
+    }
+
     let mut any_standing = None;
+    let mut num_toppleables = 0;
     let mut num_still_standing = 0;
-    let mut all_standing = true;
-    for (entity, toppleable) in query.iter() {
+    let mut any_falling = false;
+    let mut goal_reached = false;
+    for (entity, toppleable, must_stay_standing, _, _, position, laid_flat) in query.iter() {
+        if must_stay_standing || laid_flat {
+            continue;
+        }
+        num_toppleables += 1;
         match toppleable {
             Toppleable::Standing => {
                 any_standing = Some(entity);
                 num_still_standing += 1;
+                continue;
             }
             Toppleable::Falling { .. } => {
-                // No need to set `all_standing = false;` since nothing will look at it.
-                return;
-            }
-            Toppleable::Stopped | Toppleable::FellOut => {
-                all_standing = false;
+                any_falling = true;
             }
+            Toppleable::Stopped | Toppleable::FellOut => {}
+        }
+        if goal_regions_query
+            .iter()
+            .any(|transform| is_in_transformed_unit_square(transform, position.0))
+        {
+            goal_reached = true;
         }
     }
-    if all_standing {
+    if num_still_standing == num_toppleables {
+        topple_clock.0 = Duration::ZERO;
         return;
     }
+    topple_clock.0 += time.delta();
 
-    if let Some(standing_entity) = any_standing {
-        for entity in camera_target_query.iter() {
-            commands.entity(entity).remove::<CameraTarget>();
+    let win_condition = win_condition_query
+        .iter()
+        .next()
+        .cloned()
+        .unwrap_or_default();
+    let outcome = match win_condition {
+        WinCondition::ToppleEverything => {
+            if any_falling {
+                return;
+            }
+            if num_still_standing == 0 {
+                Ok(())
+            } else {
+                Err(GameOverReason::TilesStillStanding(num_still_standing))
+            }
         }
-        commands.entity(standing_entity).insert(CameraTarget);
-        *game_over_reason = GameOverReason::TilesStillStanding(num_still_standing);
-        app_state.set(AppState::GameOver);
-    } else {
-        app_state.set(AppState::LevelCompleted);
+        WinCondition::ToppleAtLeast { percent } => {
+            if any_falling {
+                return;
+            }
+            let num_toppled = num_toppleables - num_still_standing;
+            let num_required = (0.01 * percent * num_toppleables as f32).ceil() as usize;
+            if num_required <= num_toppled {
+                Ok(())
+            } else {
+                Err(GameOverReason::TooFewToppled {
+                    num_toppled,
+                    num_required,
+                })
+            }
+        }
+        WinCondition::ToppleEverythingWithin { seconds } => {
+            if 0 < num_still_standing && seconds < topple_clock.0.as_secs_f32() {
+                Err(GameOverReason::ToppledTooSlowly(seconds))
+            } else if any_falling {
+                return;
+            } else if num_still_standing == 0 {
+                Ok(())
+            } else {
+                Err(GameOverReason::TilesStillStanding(num_still_standing))
+            }
+        }
+        WinCondition::ReachGoal | WinCondition::HitGoal => {
+            let goal_reached = if win_condition == WinCondition::HitGoal {
+                !goals_hit_query.is_empty()
+            } else {
+                goal_reached
+            };
+            if goal_reached {
+                Ok(())
+            } else if any_falling {
+                return;
+            } else {
+                Err(GameOverReason::GoalNotReached)
+            }
+        }
+    };
+
+    match outcome {
+        Ok(()) => {
+            app_state.set(AppState::LevelCompleted);
+        }
+        Err(reason) => {
+            if let Some(standing_entity) = any_standing {
+                for entity in camera_target_query.iter() {
+                    commands.entity(entity).remove::<CameraTarget>();
+                }
+                commands.entity(standing_entity).insert(CameraTarget);
+            }
+            *game_over_reason = reason;
+            app_state.set(AppState::GameOver);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tilted_flat_bricks_do_not_take_the_camera() {
+        let mut app = App::new();
+        app.init_resource::<Time>();
+        app.add_systems(Update, update_toppleable);
+        let player = app.world_mut().spawn(CameraTarget).id();
+        let brick = app
+            .world_mut()
+            .spawn((
+                Toppleable::Standing,
+                LaidFlat,
+                Rotation::degrees(20.0),
+                StartingRotation(Rotation::degrees(90.0)),
+                LinearVelocity::ZERO,
+                AngularVelocity::ZERO,
+            ))
+            .id();
+        app.update();
+        assert!(app.world().entity(player).contains::<CameraTarget>());
+        assert!(!app.world().entity(brick).contains::<CameraTarget>());
+        assert!(matches!(
+            app.world().get::<Toppleable>(brick),
+            Some(Toppleable::Standing)
+        ));
     }
 }
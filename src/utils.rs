@@ -2,7 +2,6 @@ use avian2d::prelude::*;
 use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
 
-#[allow(unused)]
 pub fn collision_started_events_both_ways<'a>(
     reader: &'a mut EventReader<CollisionStarted>,
 ) -> impl 'a + Iterator<Item = (Entity, Entity)> {
@@ -11,6 +10,15 @@ pub fn collision_started_events_both_ways<'a>(
         .flat_map(|CollisionStarted(e1, e2)| [(*e1, *e2), (*e2, *e1)])
 }
 
+/// Whether the point is inside the unit square, after it gets transformed.
+pub fn is_in_transformed_unit_square(transform: &GlobalTransform, point: Vec2) -> bool {
+    let local = transform
+        .affine()
+        .inverse()
+        .transform_point3(point.extend(0.0));
+    local.x.abs() <= 0.5 && local.y.abs() <= 0.5
+}
+
 #[derive(SystemParam)]
 pub struct CachedPbrMaker<'w, 's> {
     meshes: ResMut<'w, Assets<Mesh>>,
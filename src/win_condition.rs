@@ -0,0 +1,219 @@
+use std::time::Duration;
+
+use avian2d::prelude::*;
+use bevy::color::palettes::css;
+use bevy::prelude::*;
+use bevy_yoleck::prelude::*;
+use bevy_yoleck::vpeol_3d::{Vpeol3dPosition, Vpeol3dRotation, Vpeol3dScale};
+use serde::{Deserialize, Serialize};
+
+use crate::During;
+use crate::arena::resize_rectangle;
+use crate::rewind::Rewind;
+use crate::topple_detection::Toppleable;
+use crate::utils::{CachedPbrMaker, collision_started_events_both_ways};
+
+pub struct WinConditionPlugin;
+
+impl Plugin for WinConditionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_yoleck_entity_type({
+            YoleckEntityType::new("WinCondition")
+                .with::<Vpeol3dPosition>()
+                .with::<WinCondition>()
+        });
+        app.add_yoleck_entity_type({
+            YoleckEntityType::new("GoalRegion")
+                .with::<Vpeol3dPosition>()
+                .with::<Vpeol3dScale>()
+                .with::<Vpeol3dRotation>()
+                .insert_on_init(|| IsGoalRegion)
+        });
+        app.add_yoleck_entity_type({
+            YoleckEntityType::new("Goal")
+                .with::<Vpeol3dPosition>()
+                .insert_on_init(|| IsGoal)
+        });
+        app.init_resource::<GoalAssets>();
+
+        app.add_yoleck_edit_system(edit_win_condition);
+        app.add_yoleck_edit_system(resize_rectangle::<With<IsGoalRegion>>);
+
+        app.add_systems(
+            YoleckSchedule::Populate,
+            (populate_win_condition, populate_goal_region, populate_goal),
+        );
+        app.add_systems(FixedUpdate, detect_goal_hits.in_set(During::Gameplay));
+        app.add_observer(reset_goals_on_rewind);
+    }
+}
+
+/// What the toppling needs to achieve for the level to be completed. Levels without a
+/// `WinCondition` entity use [`WinCondition::ToppleEverything`].
+#[derive(Component, YoleckComponent, Serialize, Deserialize, PartialEq, Clone, Debug, Default)]
+pub enum WinCondition {
+    #[default]
+    ToppleEverything,
+    ToppleAtLeast {
+        percent: f32,
+    },
+    ToppleEverythingWithin {
+        seconds: f32,
+    },
+    /// At least one toppled brick needs to end up inside a `GoalRegion`.
+    ReachGoal,
+    /// A falling brick needs to hit a `Goal`.
+    HitGoal,
+}
+
+#[derive(Component)]
+pub struct IsGoalRegion;
+
+fn edit_win_condition(mut ui: ResMut<YoleckUi>, mut edit: YoleckEdit<&mut WinCondition>) {
+    let Ok(mut win_condition) = edit.single_mut() else {
+        return;
+    };
+    for (label, option) in [
+        ("Topple everything", WinCondition::ToppleEverything),
+        (
+            "Topple at least a percentage",
+            WinCondition::ToppleAtLeast { percent: 80.0 },
+        ),
+        (
+            "Topple everything in time",
+            WinCondition::ToppleEverythingWithin { seconds: 10.0 },
+        ),
+        ("Reach a goal region", WinCondition::ReachGoal),
+        ("Hit a goal", WinCondition::HitGoal),
+    ] {
+        let is_selected =
+            std::mem::discriminant(win_condition.as_ref()) == std::mem::discriminant(&option);
+        if ui.radio(is_selected, label).clicked() && !is_selected {
+            *win_condition = option;
+        }
+    }
+    match win_condition.as_mut() {
+        WinCondition::ToppleEverything | WinCondition::ReachGoal | WinCondition::HitGoal => {}
+        WinCondition::ToppleAtLeast { percent } => {
+            ui.add(egui::Slider::new(percent, 1.0..=100.0).text("Percent"));
+        }
+        WinCondition::ToppleEverythingWithin { seconds } => {
+            ui.add(egui::Slider::new(seconds, 0.5..=60.0).text("Seconds after first fall"));
+        }
+    }
+}
+
+fn populate_win_condition(
+    mut populate: YoleckPopulate<(), With<WinCondition>>,
+    mut pbr: CachedPbrMaker,
+) {
+    populate.populate(|ctx, mut cmd, ()| {
+        // Only there so that it can be selected in the editor.
+        if ctx.is_first_time() && ctx.is_in_editor() {
+            cmd.insert(pbr.make_pbr_with(
+                || Mesh::from(Cuboid::new(1.0, 1.0, 1.0)),
+                || StandardMaterial::from_color(css::GOLD),
+            ));
+        }
+    });
+}
+
+fn populate_goal_region(
+    mut populate: YoleckPopulate<(), With<IsGoalRegion>>,
+    mut pbr: CachedPbrMaker,
+) {
+    populate.populate(|ctx, mut cmd, ()| {
+        if ctx.is_first_time() {
+            cmd.insert(pbr.make_pbr_with(
+                || Mesh::from(Cuboid::new(1.0, 1.0, 0.1)),
+                || StandardMaterial {
+                    base_color: css::LIME.with_alpha(0.3).into(),
+                    alpha_mode: AlphaMode::Blend,
+                    ..Default::default()
+                },
+            ));
+        }
+    });
+}
+
+/// A bell that rings when a falling brick hits it.
+#[derive(Component)]
+pub struct IsGoal;
+
+#[derive(Component)]
+pub struct GoalHit;
+
+#[derive(Resource)]
+struct GoalAssets {
+    mesh: Mesh3d,
+    idle_material: MeshMaterial3d<StandardMaterial>,
+    hit_material: MeshMaterial3d<StandardMaterial>,
+    chime: Vec<Handle<Pitch>>,
+}
+
+impl FromWorld for GoalAssets {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            mesh: Mesh3d(world.add_asset::<Mesh>(Sphere::new(0.5))),
+            idle_material: MeshMaterial3d(world.add_asset(StandardMaterial::from_color(css::GOLD))),
+            hit_material: MeshMaterial3d(world.add_asset(StandardMaterial {
+                base_color: css::GOLD.into(),
+                emissive: LinearRgba::rgb(8.0, 6.0, 0.0),
+                ..Default::default()
+            })),
+            // A major chord, so that it sounds like a success.
+            chime: [523.25, 659.25, 783.99]
+                .into_iter()
+                .map(|frequency| world.add_asset(Pitch::new(frequency, Duration::from_millis(600))))
+                .collect(),
+        }
+    }
+}
+
+fn populate_goal(mut populate: YoleckPopulate<(), With<IsGoal>>, goal_assets: Res<GoalAssets>) {
+    populate.populate(|ctx, mut cmd, ()| {
+        if ctx.is_first_time() {
+            cmd.insert((goal_assets.mesh.clone(), goal_assets.idle_material.clone()));
+            cmd.insert(RigidBody::Static);
+            cmd.insert(Collider::circle(0.5));
+            cmd.insert(CollisionEventsEnabled);
+        }
+    });
+}
+
+fn detect_goal_hits(
+    mut reader: EventReader<CollisionStarted>,
+    goals_query: Query<(), (With<IsGoal>, Without<GoalHit>)>,
+    toppleables_query: Query<&Toppleable>,
+    goal_assets: Res<GoalAssets>,
+    mut commands: Commands,
+) {
+    for (goal, other) in collision_started_events_both_ways(&mut reader) {
+        if !goals_query.contains(goal) {
+            continue;
+        }
+        let Ok(Toppleable::Falling { .. }) = toppleables_query.get(other) else {
+            continue;
+        };
+        commands
+            .entity(goal)
+            .insert((GoalHit, goal_assets.hit_material.clone()));
+        for pitch in goal_assets.chime.iter() {
+            commands.spawn((AudioPlayer(pitch.clone()), PlaybackSettings::DESPAWN));
+        }
+    }
+}
+
+fn reset_goals_on_rewind(
+    _: Trigger<Rewind>,
+    goals_query: Query<Entity, (With<IsGoal>, With<GoalHit>)>,
+    goal_assets: Res<GoalAssets>,
+    mut commands: Commands,
+) {
+    for goal in goals_query.iter() {
+        commands
+            .entity(goal)
+            .remove::<GoalHit>()
+            .insert(goal_assets.idle_material.clone());
+    }
+}
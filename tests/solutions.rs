@@ -0,0 +1,86 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use avian2d::PhysicsPlugins;
+use bevy::log::LogPlugin;
+use bevy::prelude::*;
+use bevy_enhanced_input::EnhancedInputPlugin;
+use bevy_tnua::prelude::TnuaControllerPlugin;
+use bevy_tnua_avian2d::TnuaAvian2dPlugin;
+use bevy_yoleck::YoleckPluginForGame;
+use bevy_yoleck::vpeol_3d::Vpeol3dPluginForGame;
+use time_to_topple::{GameOverReason, ReplayMode, TimeToTopplePlugin, headless_default_plugins};
+
+fn levels_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("assets")
+        .join("levels")
+}
+
+fn level_filenames() -> Vec<String> {
+    let index_file = std::fs::File::open(levels_dir().join("index.yoli")).unwrap();
+    let index: serde_json::Value = serde_json::from_reader(index_file).unwrap();
+    index[1]
+        .as_array()
+        .expect("level index should list the levels as its second element")
+        .iter()
+        .map(|level| level["filename"].as_str().unwrap().to_owned())
+        .collect()
+}
+
+fn play_solution(level: &str) -> Result<(), String> {
+    let mut app = App::new();
+    app.add_plugins(headless_default_plugins().disable::<LogPlugin>());
+    app.add_plugins((
+        PhysicsPlugins::default(),
+        TnuaControllerPlugin::new(FixedUpdate),
+        TnuaAvian2dPlugin::new(FixedUpdate),
+    ));
+    app.add_plugins(EnhancedInputPlugin);
+    app.add_plugins((YoleckPluginForGame, Vpeol3dPluginForGame));
+    app.add_plugins(TimeToTopplePlugin {
+        is_editor: false,
+        start_at_level: Some(level.to_owned()),
+        headless_timeout: Some(Duration::from_secs(60)),
+        replay_mode: ReplayMode::PlaySolution,
+    });
+    app.finish();
+    app.cleanup();
+
+    loop {
+        app.update();
+        match app.should_exit() {
+            None => {}
+            Some(AppExit::Success) => return Ok(()),
+            Some(AppExit::Error(_)) => {
+                return Err(app
+                    .world()
+                    .resource::<GameOverReason>()
+                    .description()
+                    .unwrap_or_else(|| "timed out".to_owned()));
+            }
+        }
+    }
+}
+
+#[test]
+fn all_levels_are_solved_by_their_solutions() {
+    let mut failures = Vec::new();
+    for level in level_filenames() {
+        let solution_path = levels_dir().join(format!(
+            "{}.solution",
+            level.strip_suffix(".yol").unwrap_or(&level)
+        ));
+        if !solution_path.exists() {
+            failures.push(format!(
+                "{level}: no solution (record one with `--level {level} --record <DIR>` and move \
+                 the `.replay` file here as `.solution`)"
+            ));
+            continue;
+        }
+        if let Err(reason) = play_solution(&level) {
+            failures.push(format!("{level}: {reason}"));
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}